@@ -0,0 +1,120 @@
+// benches/tag_query.rs
+//
+// Tagged quote lookup on a 100k-quote database: the old temp table joined
+// on LOWER(tag) against the indexed tag_norm column queried through
+// json_each. Run with `cargo bench --bench tag_query`.
+use criterion::{criterion_group, criterion_main, Criterion};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::SqlitePool;
+use std::str::FromStr;
+use tokio::runtime::Runtime;
+
+const QUOTES: usize = 100_000;
+const TAGS: usize = 500;
+const TAGS_PER_QUOTE: usize = 3;
+
+async fn seeded_db() -> SqlitePool {
+    let options = SqliteConnectOptions::from_str("sqlite::memory:").unwrap();
+    let db = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(options)
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations").run(&db).await.unwrap();
+
+    let mut tx = db.begin().await.unwrap();
+    for n in 0..QUOTES {
+        let id = format!("quote-{}", n);
+        sqlx::query("INSERT INTO quotes (id, whos_there, answer_who, source) VALUES ($1, $2, $3, '')")
+            .bind(&id)
+            .bind(format!("Player {}", n % 1000))
+            .bind(format!("Quote number {}", n))
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        for k in 0..TAGS_PER_QUOTE {
+            let tag = format!("Team {}", (n * 7 + k * 131) % TAGS);
+            sqlx::query("INSERT OR IGNORE INTO quote_tags (quote_id, tag, tag_norm) VALUES ($1, $2, $3)")
+                .bind(&id)
+                .bind(&tag)
+                .bind(tag.to_lowercase())
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+        }
+    }
+    tx.commit().await.unwrap();
+    db
+}
+
+async fn temp_table_lookup(db: &SqlitePool, tags: &[&str]) -> Option<String> {
+    let mut tx = db.begin().await.unwrap();
+    sqlx::query("DROP TABLE IF EXISTS temp_search_tags;")
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    sqlx::query("CREATE TEMPORARY TABLE temp_search_tags (tag_query TEXT);")
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    for tag in tags {
+        sqlx::query("INSERT INTO temp_search_tags (tag_query) VALUES ($1);")
+            .bind(tag.trim().to_lowercase())
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+    }
+    let found = sqlx::query_scalar(
+        "SELECT qt.quote_id
+        FROM quote_tags qt
+        JOIN quotes q ON q.id = qt.quote_id AND q.deleted_at IS NULL
+        JOIN temp_search_tags tst ON LOWER(qt.tag) = tst.tag_query
+        GROUP BY qt.quote_id
+        HAVING COUNT(DISTINCT tst.tag_query) = (SELECT COUNT(*) FROM temp_search_tags)
+        ORDER BY RANDOM()
+        LIMIT 1;",
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .unwrap();
+    tx.commit().await.unwrap();
+    found
+}
+
+async fn tag_norm_lookup(db: &SqlitePool, tags: &[&str]) -> Option<String> {
+    let tags: Vec<String> = tags.iter().map(|tag| tag.trim().to_lowercase()).collect();
+    sqlx::query_scalar(
+        "SELECT qt.quote_id
+        FROM quote_tags qt
+        JOIN quotes q ON q.id = qt.quote_id AND q.deleted_at IS NULL
+        WHERE qt.tag_norm IN (SELECT value FROM json_each($1))
+        GROUP BY qt.quote_id
+        HAVING COUNT(*) = json_array_length($1)
+        ORDER BY RANDOM()
+        LIMIT 1;",
+    )
+    .bind(sqlx::types::Json(tags))
+    .fetch_optional(db)
+    .await
+    .unwrap()
+}
+
+fn tagged_quote(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let db = runtime.block_on(seeded_db());
+    let search = ["team 7", "TEAM 138"];
+    assert!(runtime.block_on(tag_norm_lookup(&db, &search)).is_some());
+
+    let mut group = c.benchmark_group("tagged_quote_100k");
+    group.sample_size(20);
+    group.bench_function("temp_table_lower", |b| {
+        b.to_async(&runtime).iter(|| temp_table_lookup(&db, &search))
+    });
+    group.bench_function("tag_norm_json_each", |b| {
+        b.to_async(&runtime).iter(|| tag_norm_lookup(&db, &search))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, tagged_quote);
+criterion_main!(benches);
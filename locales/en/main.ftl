@@ -0,0 +1,69 @@
+# Web UI strings. Every message here must also exist in each other locale.
+
+locale-name = English
+
+## Shared
+
+site-title = Quote Server
+theme-label = Theme:
+language-label = Language:
+
+## Quote page
+
+index-heading = Quote Server: NBA Athlete Quotes
+quote-says = says:
+quote-source = Quote Source:
+quote-tags = Tags:
+tags-filter-label = Filter by Tags (separated by comma, e.g., Los Angeles Lakers, lakers):
+tags-filter-placeholder = e.g., Los Angeles Lakers, lakers
+tags-filter-button = Get Quote by Tags / Random Quotes
+another-quote-link = *Get another random quote*
+suggest-link = *Suggest a quote*
+
+# Shown in place of a quote when none could be loaded.
+fallback-speaker = Oh no!
+fallback-text = The quote you were looking for decided to take a day off. Try another!
+fallback-source = The Server
+
+## Attribution
+
+attribution-said-on = Said on:
+attribution-event = Event:
+attribution-medium = Medium:
+attribution-verification = Verification:
+medium-interview = Interview
+medium-press_conference = Press conference
+medium-broadcast = Broadcast
+medium-speech = Speech
+medium-book = Book
+medium-article = Article
+medium-tweet = Tweet
+medium-other = Other
+verification-unverified = Unverified
+verification-verified = Verified
+verification-disputed = Disputed
+verification-misattributed = Misattributed
+
+## Votes
+
+vote-up = Upvote
+vote-down = Downvote
+# Tooltip on the button of the vote already cast, which takes it back.
+vote-withdraw = Click again to withdraw your vote
+
+## Submission page
+
+submit-title = Quote Server: Suggest a Quote
+submit-heading = Suggest a Quote
+submit-intro = Suggestions are reviewed by an editor before they appear on the site.
+submit-id-label = Short id (e.g., shaq-0):
+submit-speaker-label = Who said it:
+submit-quote-label = Quote:
+submit-source-label = Source URL:
+submit-tags-label = Tags (separated by comma):
+submit-honeypot-label = Leave this field empty:
+submit-button = Submit for Review
+back-link = *Back to the quotes*
+submit-thanks = Thanks! Your quote has been sent to our editors for review.
+submit-failed = Something went wrong, please try again later.
+submit-rejected = Your quote was not accepted: { $reason }.
@@ -0,0 +1,69 @@
+# Textos de la interfaz web. Cada mensaje debe existir también en los demás idiomas.
+
+locale-name = Español
+
+## Compartidos
+
+site-title = Servidor de Citas
+theme-label = Tema:
+language-label = Idioma:
+
+## Página de citas
+
+index-heading = Servidor de Citas: citas de atletas de la NBA
+quote-says = dice:
+quote-source = Fuente de la cita:
+quote-tags = Etiquetas:
+tags-filter-label = Filtrar por etiquetas (separadas por comas, p. ej., Los Angeles Lakers, lakers):
+tags-filter-placeholder = p. ej., Los Angeles Lakers, lakers
+tags-filter-button = Buscar cita por etiquetas / citas al azar
+another-quote-link = *Ver otra cita al azar*
+suggest-link = *Sugerir una cita*
+
+# Se muestra en lugar de una cita cuando no se pudo cargar ninguna.
+fallback-speaker = ¡Oh, no!
+fallback-text = La cita que buscabas decidió tomarse el día libre. ¡Prueba con otra!
+fallback-source = El Servidor
+
+## Atribución
+
+attribution-said-on = Fecha:
+attribution-event = Evento:
+attribution-medium = Medio:
+attribution-verification = Verificación:
+medium-interview = Entrevista
+medium-press_conference = Rueda de prensa
+medium-broadcast = Transmisión
+medium-speech = Discurso
+medium-book = Libro
+medium-article = Artículo
+medium-tweet = Tuit
+medium-other = Otro
+verification-unverified = Sin verificar
+verification-verified = Verificada
+verification-disputed = Disputada
+verification-misattributed = Mal atribuida
+
+## Votos
+
+vote-up = Me gusta
+vote-down = No me gusta
+# Aparece sobre el botón del voto ya emitido, que lo retira.
+vote-withdraw = Vuelve a pulsar para retirar tu voto
+
+## Página de sugerencias
+
+submit-title = Servidor de Citas: sugerir una cita
+submit-heading = Sugerir una cita
+submit-intro = Un editor revisa las sugerencias antes de que aparezcan en el sitio.
+submit-id-label = Id corto (p. ej., shaq-0):
+submit-speaker-label = Quién lo dijo:
+submit-quote-label = Cita:
+submit-source-label = URL de la fuente:
+submit-tags-label = Etiquetas (separadas por comas):
+submit-honeypot-label = Deja este campo vacío:
+submit-button = Enviar para revisión
+back-link = *Volver a las citas*
+submit-thanks = ¡Gracias! Tu cita se envió a nuestros editores para su revisión.
+submit-failed = Algo salió mal, inténtalo de nuevo más tarde.
+submit-rejected = Tu cita no fue aceptada: { $reason }.
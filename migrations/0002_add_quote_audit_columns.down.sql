@@ -0,0 +1,6 @@
+-- migration reverts the audit columns on 'quotes'.
+
+ALTER TABLE quotes DROP COLUMN updated_by;
+ALTER TABLE quotes DROP COLUMN created_by;
+ALTER TABLE quotes DROP COLUMN updated_at;
+ALTER TABLE quotes DROP COLUMN created_at;
@@ -0,0 +1,7 @@
+-- audit columns recording when and by whom a quote was last written.
+-- rows that predate this migration keep NULLs: their history is unknown.
+
+ALTER TABLE quotes ADD COLUMN created_at DATETIME;
+ALTER TABLE quotes ADD COLUMN updated_at DATETIME;
+ALTER TABLE quotes ADD COLUMN created_by VARCHAR(255);
+ALTER TABLE quotes ADD COLUMN updated_by VARCHAR(255);
@@ -0,0 +1,5 @@
+-- migration reverts the creation of the 'quote_revisions' table.
+
+DROP TRIGGER IF EXISTS quote_revisions_no_delete;
+DROP TRIGGER IF EXISTS quote_revisions_no_update;
+DROP TABLE IF EXISTS quote_revisions;
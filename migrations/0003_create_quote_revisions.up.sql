@@ -0,0 +1,35 @@
+-- immutable history of every write to a quote and its tags.
+-- quote_id is deliberately not a foreign key so history outlives deletes.
+
+CREATE TABLE IF NOT EXISTS quote_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    quote_id VARCHAR(255) NOT NULL,
+    revision INTEGER NOT NULL,
+    action VARCHAR(16) NOT NULL,
+    whos_there VARCHAR(255) NOT NULL,
+    answer_who TEXT NOT NULL,
+    source VARCHAR(512) NOT NULL,
+    tags TEXT NOT NULL,
+    author VARCHAR(255),
+    created_at DATETIME NOT NULL,
+    UNIQUE (quote_id, revision)
+);
+
+CREATE TRIGGER IF NOT EXISTS quote_revisions_no_update
+BEFORE UPDATE ON quote_revisions
+BEGIN
+    SELECT RAISE(ABORT, 'quote revisions are immutable');
+END;
+
+CREATE TRIGGER IF NOT EXISTS quote_revisions_no_delete
+BEFORE DELETE ON quote_revisions
+BEGIN
+    SELECT RAISE(ABORT, 'quote revisions are immutable');
+END;
+
+-- seed history with the current state of every existing quote.
+INSERT INTO quote_revisions (quote_id, revision, action, whos_there, answer_who, source, tags, author, created_at)
+SELECT q.id, 1, 'create', q.whos_there, q.answer_who, q.source,
+    (SELECT json_group_array(tag) FROM (SELECT tag FROM quote_tags WHERE quote_id = q.id ORDER BY tag)),
+    q.created_by, COALESCE(q.created_at, CURRENT_TIMESTAMP)
+FROM quotes q;
@@ -0,0 +1,5 @@
+-- migration reverts the soft-delete columns on 'quotes'.
+
+DROP INDEX IF EXISTS idx_quotes_deleted_at;
+ALTER TABLE quotes DROP COLUMN deleted_by;
+ALTER TABLE quotes DROP COLUMN deleted_at;
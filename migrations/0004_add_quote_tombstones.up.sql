@@ -0,0 +1,7 @@
+-- soft delete: a quote with deleted_at set is in the trash and hidden from
+-- every lookup until it is restored or purged.
+
+ALTER TABLE quotes ADD COLUMN deleted_at DATETIME;
+ALTER TABLE quotes ADD COLUMN deleted_by VARCHAR(255);
+
+CREATE INDEX IF NOT EXISTS idx_quotes_deleted_at ON quotes (deleted_at);
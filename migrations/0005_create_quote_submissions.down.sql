@@ -0,0 +1,3 @@
+-- migration reverts the creation of the 'quote_submissions' table.
+
+DROP TABLE IF EXISTS quote_submissions;
@@ -0,0 +1,19 @@
+-- quotes suggested by the public, waiting for an editor to approve or reject them.
+
+CREATE TABLE IF NOT EXISTS quote_submissions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    quote_id VARCHAR(255) NOT NULL,
+    whos_there VARCHAR(255) NOT NULL,
+    answer_who TEXT NOT NULL,
+    source VARCHAR(512) NOT NULL,
+    tags TEXT NOT NULL,
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    submitter_ip VARCHAR(64),
+    submitted_at DATETIME NOT NULL,
+    reviewed_at DATETIME,
+    reviewed_by VARCHAR(255),
+    reject_reason TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_quote_submissions_status ON quote_submissions (status);
+CREATE INDEX IF NOT EXISTS idx_quote_submissions_ip ON quote_submissions (submitter_ip, submitted_at);
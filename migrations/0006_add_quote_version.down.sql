@@ -0,0 +1,3 @@
+-- migration reverts the version column on 'quotes'.
+
+ALTER TABLE quotes DROP COLUMN version;
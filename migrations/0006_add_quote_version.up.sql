@@ -0,0 +1,3 @@
+-- revision counter bumped on every write, used for optimistic concurrency.
+
+ALTER TABLE quotes ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,3 @@
+-- migration reverts the creation of the 'idempotency_keys' table.
+
+DROP TABLE IF EXISTS idempotency_keys;
@@ -0,0 +1,14 @@
+-- responses to POST requests sent with an Idempotency-Key, replayed on retries.
+
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    scope VARCHAR(320) NOT NULL,
+    idempotency_key VARCHAR(255) NOT NULL,
+    fingerprint VARCHAR(64) NOT NULL,
+    status_code INTEGER,
+    content_type VARCHAR(255),
+    body BLOB,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (scope, idempotency_key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created_at ON idempotency_keys (created_at);
@@ -0,0 +1,5 @@
+-- migration reverts the normalized tag column on 'quote_tags'.
+
+DROP INDEX IF EXISTS idx_quote_tags_tag_norm;
+DROP INDEX IF EXISTS idx_quote_tags_quote_norm;
+ALTER TABLE quote_tags DROP COLUMN tag_norm;
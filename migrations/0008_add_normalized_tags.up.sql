@@ -0,0 +1,13 @@
+-- normalized form of each tag, used for matching so lookups can use an index.
+-- SQLite's LOWER only folds ASCII; other tags are renormalized on their next write.
+
+DELETE FROM quote_tags WHERE id NOT IN (
+    SELECT MIN(id) FROM quote_tags GROUP BY quote_id, LOWER(TRIM(tag))
+);
+
+ALTER TABLE quote_tags ADD COLUMN tag_norm VARCHAR(255) NOT NULL DEFAULT '';
+
+UPDATE quote_tags SET tag = TRIM(tag), tag_norm = LOWER(TRIM(tag));
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_quote_tags_quote_norm ON quote_tags (quote_id, tag_norm);
+CREATE INDEX IF NOT EXISTS idx_quote_tags_tag_norm ON quote_tags (tag_norm, quote_id);
@@ -0,0 +1,14 @@
+-- translated punchlines, one per quote and language. `lang` is a lowercased
+-- BCP-47 tag such as 'es' or 'pt-br'.
+
+CREATE TABLE IF NOT EXISTS quote_translations (
+    quote_id VARCHAR(255) NOT NULL,
+    lang VARCHAR(35) NOT NULL,
+    answer_who TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    created_by VARCHAR(255) NOT NULL,
+    updated_by VARCHAR(255) NOT NULL,
+    PRIMARY KEY (quote_id, lang),
+    FOREIGN KEY (quote_id) REFERENCES quotes(id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- migration reverts the attribution columns on 'quotes' and 'quote_revisions'.
+
+ALTER TABLE quote_revisions DROP COLUMN attribution;
+DROP INDEX IF EXISTS idx_quotes_said_on;
+ALTER TABLE quotes DROP COLUMN verification;
+ALTER TABLE quotes DROP COLUMN medium;
+ALTER TABLE quotes DROP COLUMN event;
+ALTER TABLE quotes DROP COLUMN said_on;
@@ -0,0 +1,13 @@
+-- when, where and how a quote was said. said_on is a partial ISO date
+-- ('2008', '2008-06' or '2008-06-17'), so it sorts and range-filters as text.
+
+ALTER TABLE quotes ADD COLUMN said_on VARCHAR(10);
+ALTER TABLE quotes ADD COLUMN event VARCHAR(255);
+ALTER TABLE quotes ADD COLUMN medium VARCHAR(32);
+ALTER TABLE quotes ADD COLUMN verification VARCHAR(16) NOT NULL DEFAULT 'unverified';
+
+CREATE INDEX IF NOT EXISTS idx_quotes_said_on ON quotes (said_on);
+
+-- revisions snapshot the attribution as one JSON object; NULL for revisions
+-- written before it existed.
+ALTER TABLE quote_revisions ADD COLUMN attribution TEXT;
@@ -0,0 +1,4 @@
+-- migration reverts the creation of the 'quote_sources' table.
+
+ALTER TABLE quote_revisions DROP COLUMN sources;
+DROP TABLE IF EXISTS quote_sources;
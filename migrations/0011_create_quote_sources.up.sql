@@ -0,0 +1,20 @@
+-- citations backing a quote, in the order they are listed. accessed_on is an
+-- ISO date; kind is 'primary' (the speaker's own words on record) or
+-- 'secondary' (a report of them).
+
+CREATE TABLE IF NOT EXISTS quote_sources (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    quote_id VARCHAR(255) NOT NULL,
+    position INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    title TEXT,
+    publisher VARCHAR(255),
+    accessed_on DATE,
+    kind VARCHAR(16) NOT NULL DEFAULT 'secondary',
+    UNIQUE (quote_id, position),
+    FOREIGN KEY (quote_id) REFERENCES quotes(id) ON DELETE CASCADE
+);
+
+-- revisions snapshot the sources as a JSON array; NULL for revisions written
+-- before they existed.
+ALTER TABLE quote_revisions ADD COLUMN sources TEXT;
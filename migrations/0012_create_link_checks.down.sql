@@ -0,0 +1,3 @@
+-- migration reverts the creation of the 'link_checks' table.
+
+DROP TABLE IF EXISTS link_checks;
@@ -0,0 +1,16 @@
+-- latest result of checking each source link, keyed by URL so a link cited
+-- by several quotes is fetched once. status is the final HTTP status, NULL
+-- when no response came back; redirects is a JSON array of the URLs
+-- followed, in order. broken_since is kept while a link stays broken.
+
+CREATE TABLE IF NOT EXISTS link_checks (
+    url TEXT PRIMARY KEY NOT NULL,
+    status INTEGER,
+    error TEXT,
+    redirects TEXT NOT NULL DEFAULT '[]',
+    broken BOOLEAN NOT NULL,
+    checked_at DATETIME NOT NULL,
+    broken_since DATETIME
+);
+
+CREATE INDEX IF NOT EXISTS idx_link_checks_checked_at ON link_checks (checked_at);
@@ -0,0 +1,4 @@
+-- migration reverts the 'fingerprint' column on 'quotes'.
+
+DROP INDEX IF EXISTS idx_quotes_fingerprint;
+ALTER TABLE quotes DROP COLUMN fingerprint;
@@ -0,0 +1,6 @@
+-- hash of each quote's normalized text, for spotting duplicates. Quotes
+-- written before this column existed are fingerprinted at startup.
+
+ALTER TABLE quotes ADD COLUMN fingerprint VARCHAR(32);
+
+CREATE INDEX IF NOT EXISTS idx_quotes_fingerprint ON quotes (fingerprint);
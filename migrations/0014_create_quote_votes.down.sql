@@ -0,0 +1,5 @@
+-- migration reverts the 'quote_votes' table and the vote totals on 'quotes'.
+
+ALTER TABLE quotes DROP COLUMN downvotes;
+ALTER TABLE quotes DROP COLUMN upvotes;
+DROP TABLE IF EXISTS quote_votes;
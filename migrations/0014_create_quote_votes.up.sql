@@ -0,0 +1,18 @@
+-- up and down votes on quotes, one per quote and voter. voter is
+-- 'user:<token subject>' for authenticated voters, else 'anon:' and a hash
+-- of the client address and User-Agent. value is 1 for an upvote and -1
+-- for a downvote. Each quote keeps its totals in upvotes and downvotes.
+
+CREATE TABLE IF NOT EXISTS quote_votes (
+    quote_id VARCHAR(255) NOT NULL,
+    voter VARCHAR(255) NOT NULL,
+    value INTEGER NOT NULL CHECK (value IN (1, -1)),
+    voted_at DATETIME NOT NULL,
+    PRIMARY KEY (quote_id, voter),
+    FOREIGN KEY (quote_id) REFERENCES quotes(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_quote_votes_voted_at ON quote_votes (voted_at);
+
+ALTER TABLE quotes ADD COLUMN upvotes INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE quotes ADD COLUMN downvotes INTEGER NOT NULL DEFAULT 0;
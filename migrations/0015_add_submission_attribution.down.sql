@@ -0,0 +1,6 @@
+-- migration reverts the attribution columns on 'quote_submissions'.
+
+ALTER TABLE quote_submissions DROP COLUMN verification;
+ALTER TABLE quote_submissions DROP COLUMN medium;
+ALTER TABLE quote_submissions DROP COLUMN event;
+ALTER TABLE quote_submissions DROP COLUMN said_on;
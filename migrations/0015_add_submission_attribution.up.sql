@@ -0,0 +1,6 @@
+-- attribution sent with a submission, copied onto the quote when it is approved.
+
+ALTER TABLE quote_submissions ADD COLUMN said_on VARCHAR(10);
+ALTER TABLE quote_submissions ADD COLUMN event VARCHAR(255);
+ALTER TABLE quote_submissions ADD COLUMN medium VARCHAR(32);
+ALTER TABLE quote_submissions ADD COLUMN verification VARCHAR(16) NOT NULL DEFAULT 'unverified';
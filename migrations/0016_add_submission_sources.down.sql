@@ -0,0 +1,3 @@
+-- migration reverts the 'sources' column on 'quote_submissions'.
+
+ALTER TABLE quote_submissions DROP COLUMN sources;
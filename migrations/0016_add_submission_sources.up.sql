@@ -0,0 +1,4 @@
+-- cited sources sent with a submission, as a JSON array, copied into
+-- 'quote_sources' when it is approved.
+
+ALTER TABLE quote_submissions ADD COLUMN sources TEXT NOT NULL DEFAULT '[]';
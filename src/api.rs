@@ -1,35 +1,104 @@
 // api.rs
-use crate::authjwt::{self, Claims, Registration};
-use crate::quote::{self, JsonQuote};
+use crate::authjwt::{self, AuthError, Claims, Registration};
+use crate::cache::{self, IfMatch};
+use crate::citation::{self, CitationStyle};
+use crate::error::{QuoteAddError, QuoteWriteError};
+use crate::linkcheck::{self, BrokenLink};
+use crate::logging;
+use crate::quote::{self, JsonQuote, Medium, QuoteFilter, QuoteListFilter, TrashedQuote, Verification};
+use crate::ratelimit::ClientIp;
+use crate::revision::{self, QuoteRevision, RevertRequest};
+use crate::rng::{self, QuoteRng};
+use crate::session;
+use crate::submission::{self, RejectRequest, Submission, SubmissionRequest, SubmissionStatus};
+use crate::translation::{self, QuoteTranslation, TranslationRequest};
+use crate::vote::{self, TopQuote, TopWindow, VoteDirection, VoteRequest, VoteTally};
 use crate::AppState;
 use axum::{
-    extract::{Json, Path, State},
-    http::{self, StatusCode},
+    extract::{Json, Path, Query, State},
+    http::{self, header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{get, post, put},
     Router,
 };
+use serde::Deserialize;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 pub fn router() -> Router<Arc<RwLock<AppState>>> {
     Router::new()
-        .route("/quote/{quote_id}", get(get_quote_api))
+        .route(
+            "/quote/{quote_id}",
+            get(get_quote_api).put(update_quote).delete(delete_quote),
+        )
+        .route("/quote/{quote_id}/history", get(get_quote_history))
+        .route("/quote/{quote_id}/citation", get(get_citations))
+        .route("/quote/{quote_id}/revert", post(revert_quote))
+        .route("/quote/{quote_id}/restore", post(restore_quote))
+        .route("/quote/{quote_id}/translations", get(list_translations))
+        .route(
+            "/quote/{quote_id}/translations/{lang}",
+            put(put_translation).delete(delete_translation),
+        )
+        .route(
+            "/quote/{quote_id}/vote",
+            get(get_votes).put(put_vote).delete(delete_vote),
+        )
+        .route("/quotes", get(list_quotes))
+        .route("/quotes/top", get(get_top_quotes))
+        .route("/trash", get(get_trash))
+        .route("/broken-links", get(get_broken_links))
+        .route("/submissions", get(list_submissions).post(submit_quote))
+        .route("/submissions/{submission_id}", put(update_submission))
+        .route("/submissions/{submission_id}/approve", post(approve_submission))
+        .route("/submissions/{submission_id}/reject", post(reject_submission))
         .route("/tagged-quote", post(get_tagged_quote_api))
         .route("/random-quote", get(get_random_quote_api))
         .route("/register", post(register))
         .route("/add-quote", post(add_quote))
 }
 
+/// Fetches a quote as JSON with cache validators, translated into the
+/// first of `languages` it has a translation for. Conditional request
+/// headers are only honoured when `request_headers` is given.
 async fn get_quote_data_for_api(
-    db: &sqlx::SqlitePool,
+    state: &AppState,
     quote_id: &str,
+    languages: &[String],
+    request_headers: Option<&HeaderMap>,
+    cache_control: &str,
 ) -> Result<Response, http::StatusCode> {
-    match quote::get_quote_by_id_from_db(db, quote_id).await {
+    logging::record_quote_id(quote_id);
+    match state.quote_index.get_quote(&state.db, quote_id).await {
         Ok((quote_obj, tags_vec)) => {
-            let json_response = JsonQuote::new(&quote_obj, tags_vec);
+            let mut json_response = JsonQuote::new(&quote_obj, tags_vec);
+            let mut last_modified = json_response.audit.updated_at;
+            match translation::find_translation(&state.db, quote_id, languages).await {
+                Ok(Some(translated)) => {
+                    json_response.answer_who = translated.answer_who;
+                    json_response.lang = Some(translated.lang);
+                    last_modified = last_modified.max(Some(translated.updated_at));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::warn!("API: translation lookup failed for id {}: {}", quote_id, e);
+                }
+            }
 
-            Ok(Json(json_response).into_response())
+            let mut response = cache::json_with_validators(
+                request_headers,
+                &json_response,
+                Some(json_response.version),
+                last_modified,
+                cache_control,
+            );
+            let headers = response.headers_mut();
+            headers.insert(header::VARY, http::HeaderValue::from_static("accept-language"));
+            if let Some(value) = json_response.lang.and_then(|lang| lang.parse().ok()) {
+                headers.insert(header::CONTENT_LANGUAGE, value);
+            }
+            Ok(response)
         }
         Err(e) => {
             tracing::warn!("API: quote fetch failed for id {}: {}", quote_id, e);
@@ -42,24 +111,69 @@ async fn get_quote_data_for_api(
     }
 }
 
+/// Page size of the quote listing, unless the client asks for another.
+const LIST_DEFAULT_LIMIT: i64 = 50;
+const LIST_MAX_LIMIT: i64 = 200;
+
+fn bad_request(message: &str) -> Response {
+    let body = Json(serde_json::json!({ "error": message }));
+    (StatusCode::BAD_REQUEST, body).into_response()
+}
+
+/// The version a write must find, from the request's `If-Match` header.
+fn expected_version(headers: &HeaderMap, required: bool) -> Result<Option<i64>, QuoteWriteError> {
+    match cache::if_match(headers) {
+        IfMatch::Absent if required => Err(QuoteWriteError::PreconditionRequired),
+        IfMatch::Absent | IfMatch::Any => Ok(None),
+        IfMatch::Version(version) => Ok(Some(version)),
+        IfMatch::Unmatchable => Err(QuoteWriteError::PreconditionFailed),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LangParams {
+    lang: Option<String>,
+}
+
 #[utoipa::path(
     get,
     path = "/api/v1/quote/{quote_id}",
     responses(
-        (status = 200, description = "Get a quote by id", body = JsonQuote),
+        (status = 200, description = "Get a quote by id", body = JsonQuote,
+            headers(("content-language" = String, description = "Language of a translated quote; absent for the original text"))),
+        (status = 304, description = "Quote unchanged since the given validators"),
         (status = 404, description = "No matching quote found"),
         (status = 500, description = "Internal server error")
     ),
     params(
-        ("quote_id" = String, Path, description = "ID of the quote to retrieve")
+        ("quote_id" = String, Path, description = "ID of the quote to retrieve"),
+        ("lang" = Option<String>, Query, description = "Preferred language, ahead of Accept-Language"),
+        ("Accept-Language" = Option<String>, Header, description = "Languages to translate the quote into, if it has a translation")
     )
 )]
 pub async fn get_quote_api(
     State(app_state): State<Arc<RwLock<AppState>>>,
     Path(quote_id): Path<String>,
+    Query(params): Query<LangParams>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     let state_guard = app_state.read().await;
-    get_quote_data_for_api(&state_guard.db, &quote_id).await
+    let cache_control = &state_guard.quote_cache_control;
+    let languages = translation::preferred_languages(&headers, params.lang.as_deref());
+    get_quote_data_for_api(&state_guard, &quote_id, &languages, Some(&headers), cache_control).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeedParams {
+    seed: Option<u64>,
+}
+
+/// Echoes the seed a random pick was made with so it can be replayed.
+fn with_seed(mut response: Response, rng: &QuoteRng) -> Response {
+    response
+        .headers_mut()
+        .insert(rng::SEED_HEADER, http::HeaderValue::from(rng.seed()));
+    response
 }
 
 #[utoipa::path(
@@ -67,22 +181,32 @@ pub async fn get_quote_api(
     path = "/api/v1/tagged-quote",
     request_body = Vec<String>,
     responses(
-        (status = 200, description = "Get a quote by matching tags", body = JsonQuote),
+        (status = 200, description = "Get a quote by matching tags", body = JsonQuote,
+            headers(("x-quote-seed" = u64, description = "Seed the quote was picked with"))),
         (status = 404, description = "No quote found for the given tags"),
         (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("seed" = Option<u64>, Query, description = "Seed to replay an earlier pick with")
     )
 )]
 pub async fn get_tagged_quote_api(
     State(app_state): State<Arc<RwLock<AppState>>>,
+    Query(params): Query<SeedParams>,
     Json(tags_payload): Json<Vec<String>>,
 ) -> impl IntoResponse {
     tracing::info!("API: get tagged quote with tags: {:?}", tags_payload);
     let state_guard = app_state.read().await;
 
     let db_pool = &state_guard.db;
-    match quote::get_tagged_quote_id_from_db(db_pool, tags_payload.iter().map(String::as_str)).await
-    {
-        Ok(Some(found_quote_id)) => get_quote_data_for_api(db_pool, &found_quote_id).await,
+    let mut rng = QuoteRng::new(params.seed);
+    let search_tags = tags_payload.iter().map(String::as_str);
+    match state_guard.quote_index.tagged_id(db_pool, search_tags, &mut rng).await {
+        Ok(Some(found_quote_id)) => {
+            get_quote_data_for_api(&state_guard, &found_quote_id, &[], None, cache::NO_STORE)
+                .await
+                .map(|response| with_seed(response, &rng))
+        }
         Ok(None) => {
             tracing::info!("API: No quote found for tags: {:?}", tags_payload);
 
@@ -95,31 +219,229 @@ pub async fn get_tagged_quote_api(
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RandomQuoteParams {
+    tag: Option<String>,
+    speaker: Option<String>,
+    max_len: Option<usize>,
+    exclude: Option<String>,
+    session: Option<String>,
+    seed: Option<u64>,
+    #[serde(default)]
+    weighted: bool,
+}
+
+/// Items of a comma separated query parameter.
+fn comma_list(value: Option<&str>) -> impl Iterator<Item = &str> {
+    value
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuoteListParams {
+    tag: Option<String>,
+    speaker: Option<String>,
+    said_from: Option<String>,
+    said_to: Option<String>,
+    event: Option<String>,
+    medium: Option<Medium>,
+    verification: Option<Verification>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/quotes",
+    responses(
+        (status = 200, description = "Quotes matching every given filter, ordered by id", body = Vec<JsonQuote>),
+        (status = 400, description = "A date filter is not a partial ISO date"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("tag" = Option<String>, Query, description = "Comma separated tags the quotes must all carry"),
+        ("speaker" = Option<String>, Query, description = "Who said it, case-insensitive"),
+        ("said_from" = Option<String>, Query, description = "Said in or after this YYYY, YYYY-MM or YYYY-MM-DD"),
+        ("said_to" = Option<String>, Query, description = "Said in or before this YYYY, YYYY-MM or YYYY-MM-DD"),
+        ("event" = Option<String>, Query, description = "Text the event must contain, case-insensitive"),
+        ("medium" = Option<Medium>, Query, description = "How the quote was said"),
+        ("verification" = Option<Verification>, Query, description = "Verification status"),
+        ("limit" = Option<i64>, Query, description = "Page size, at most 200 (default: 50)"),
+        ("offset" = Option<i64>, Query, description = "Quotes to skip (default: 0)")
+    )
+)]
+pub async fn list_quotes(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Query(params): Query<QuoteListParams>,
+) -> Response {
+    let dates = [params.said_from.as_deref(), params.said_to.as_deref()];
+    if dates.into_iter().flatten().any(|date| !quote::is_partial_date(date)) {
+        return bad_request("said_from and said_to must be YYYY, YYYY-MM or YYYY-MM-DD");
+    }
+    let filter = QuoteListFilter {
+        tags: QuoteFilter::new(comma_list(params.tag.as_deref()), None, None).tags,
+        speaker: params
+            .speaker
+            .as_deref()
+            .map(quote::normalize_speaker)
+            .filter(|speaker| !speaker.is_empty()),
+        said_from: params.said_from,
+        said_to: params.said_to,
+        event: params.event.filter(|event| !event.trim().is_empty()),
+        medium: params.medium,
+        verification: params.verification,
+    };
+    let limit = params.limit.unwrap_or(LIST_DEFAULT_LIMIT).clamp(1, LIST_MAX_LIMIT);
+    let offset = params.offset.unwrap_or(0).max(0);
+
+    let state_guard = app_state.read().await;
+    match quote::list_quotes_from_db(&state_guard.db, &filter, limit, offset).await {
+        Ok(quotes) => {
+            let quotes: Vec<JsonQuote> = quotes
+                .iter()
+                .map(|(quote, tags)| JsonQuote::new(quote, tags.clone()))
+                .collect();
+            Json(quotes).into_response()
+        }
+        Err(e) => {
+            tracing::error!("API: Failed to list quotes: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/api/v1/random-quote",
     responses(
-        (status = 200, description = "Get a random quote", body = JsonQuote),
-        (status = 404, description = "No quotes available in the database"),
+        (status = 200, description = "Get a random quote", body = JsonQuote,
+            headers(
+                ("x-quote-session" = String, description = "Session token to send back for no-repeat picks"),
+                ("x-quote-seed" = u64, description = "Seed the quote was picked with")
+            )),
+        (status = 404, description = "No quote matches the filters"),
         (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("tag" = Option<String>, Query, description = "Comma separated tags the quote must all carry"),
+        ("speaker" = Option<String>, Query, description = "Who said it, case-insensitive"),
+        ("max_len" = Option<usize>, Query, description = "Longest quote text to return, in characters"),
+        ("exclude" = Option<String>, Query, description = "Comma separated quote ids to skip"),
+        ("session" = Option<String>, Query, description = "No-repeat session token; send it empty to start one"),
+        ("seed" = Option<u64>, Query, description = "Seed to replay an earlier pick with"),
+        ("weighted" = Option<bool>, Query, description = "Favour quotes with more upvotes and fewer downvotes")
     )
 )]
 pub async fn get_random_quote_api(
     State(app_state): State<Arc<RwLock<AppState>>>,
-) -> impl IntoResponse {
+    Query(params): Query<RandomQuoteParams>,
+) -> Response {
     let state_guard = app_state.read().await;
     let db_pool = &state_guard.db;
-    match quote::get_random_quote_id_from_db(db_pool).await {
-        Ok(found_quote_id) => get_quote_data_for_api(db_pool, &found_quote_id).await,
+    let mut filter = QuoteFilter::new(
+        comma_list(params.tag.as_deref()),
+        params.speaker.as_deref(),
+        params.max_len,
+    );
+    filter.exclude = comma_list(params.exclude.as_deref())
+        .map(String::from)
+        .collect();
+    filter.weighted = params.weighted;
+
+    let mut rng = QuoteRng::new(params.seed);
+    let picked = match params.session.as_deref() {
+        Some(token) => state_guard
+            .random_sessions
+            .pick(&state_guard.quote_index, db_pool, &filter, token, &mut rng)
+            .await
+            .map(|pick| (pick.quote_id, Some(pick.session))),
+        None => state_guard
+            .quote_index
+            .matching_id(db_pool, &filter, &mut rng)
+            .await
+            .map(|quote_id| (quote_id, None)),
+    };
+
+    match picked {
+        Ok((Some(found_quote_id), session)) => {
+            let response =
+                get_quote_data_for_api(&state_guard, &found_quote_id, &[], None, cache::NO_STORE)
+                    .await
+                    .into_response();
+            let mut response = with_seed(response, &rng);
+            if let Some(value) = session.and_then(|s| http::HeaderValue::from_str(&s).ok()) {
+                response.headers_mut().insert(session::SESSION_HEADER, value);
+            }
+            response
+        }
+        Ok((None, _)) => StatusCode::NOT_FOUND.into_response(),
         Err(e) => {
             tracing::warn!("API: Failed to get random quote: {}", e);
-            if matches!(e, sqlx::Error::RowNotFound) {
-                Err(StatusCode::NOT_FOUND)
-            } else {
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Size of the top quotes ranking, unless the client asks for another.
+const TOP_DEFAULT_LIMIT: i64 = 10;
+const TOP_MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct TopQuotesParams {
+    #[serde(default)]
+    window: TopWindow,
+    limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/quotes/top",
+    responses(
+        (status = 200, description = "Quotes voted on in the window, highest score first", body = Vec<TopQuote>),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("window" = Option<TopWindow>, Query, description = "Count votes cast in the last day, week, month or year, or all of them (default: all)"),
+        ("limit" = Option<i64>, Query, description = "Quotes to return, at most 100 (default: 10)")
+    )
+)]
+pub async fn get_top_quotes(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Query(params): Query<TopQuotesParams>,
+) -> Response {
+    let limit = params.limit.unwrap_or(TOP_DEFAULT_LIMIT).clamp(1, TOP_MAX_LIMIT);
+    let since = params.window.since(chrono::Utc::now());
+
+    let state_guard = app_state.read().await;
+    let db_pool = &state_guard.db;
+    let ranked = match vote::get_top_quotes_from_db(db_pool, since, limit).await {
+        Ok(ranked) => ranked,
+        Err(e) => {
+            tracing::error!("API: Failed to rank quotes: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let mut top = Vec::with_capacity(ranked.len());
+    for entry in ranked {
+        match state_guard.quote_index.get_quote(db_pool, &entry.quote_id).await {
+            Ok((quote, tags)) => top.push(TopQuote {
+                quote: JsonQuote::new(&quote, tags),
+                upvotes: entry.upvotes,
+                downvotes: entry.downvotes,
+                score: entry.upvotes - entry.downvotes,
+            }),
+            // Deleted between the two queries.
+            Err(sqlx::Error::RowNotFound) => {}
+            Err(e) => {
+                tracing::error!("API: Failed to fetch ranked quote {}: {}", entry.quote_id, e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
             }
         }
     }
+    Json(top).into_response()
 }
 
 #[utoipa::path(
@@ -142,6 +464,12 @@ pub async fn register(
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ForceParams {
+    #[serde(default)]
+    force: bool,
+}
+
 #[utoipa::path(
     post,
     path = "/api/v1/add-quote",
@@ -150,24 +478,698 @@ pub async fn register(
         (status = 201, description = "Quote added successfully"),
         (status = 400, description = "Bad request (e.g., invalid quote format)"),
         (status = 401, description = "Authentication error"),
+        (status = 409, description = "A quote with this id already exists, or the quote resembles existing ones (listed under duplicates)"),
+        (status = 422, description = "Idempotency-Key reused with a different request body"),
         (status = 500, description = "Internal server error")
     ),
+    params(
+        ("force" = Option<bool>, Query, description = "Add the quote even if it resembles existing ones"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Makes retries of this request return the original response")
+    ),
     security(
         ("bearer_auth" = [])
     )
 )]
 pub async fn add_quote(
-    _claims: Claims,
+    claims: Claims,
     State(app_state): State<Arc<RwLock<AppState>>>,
-    Json(quote_to_add): Json<JsonQuote>,
+    Query(params): Query<ForceParams>,
+    Json(mut quote_to_add): Json<JsonQuote>,
 ) -> impl IntoResponse {
+    if let Err(message) = quote_to_add.clean() {
+        return bad_request(message);
+    }
+    let state_guard = app_state.read().await;
+    let quote_id = quote_to_add.id.clone();
+    logging::record_quote_id(&quote_id);
+    let duplicates = &state_guard.duplicates;
+    let added = async {
+        let mut tx = state_guard.db.begin().await?;
+        let known = &state_guard.trigram_index;
+        quote::add_quote_to_db(&mut tx, quote_to_add, &claims.sub, duplicates, known, params.force)
+            .await?;
+        tx.commit().await.map_err(QuoteAddError::from)
+    };
+    match added.await {
+        Ok(()) => {
+            state_guard.reindex(&quote_id).await;
+            StatusCode::CREATED.into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/quote/{quote_id}",
+    request_body = JsonQuote,
+    responses(
+        (status = 200, description = "Quote updated successfully", body = JsonQuote),
+        (status = 400, description = "Bad request (e.g., invalid quote format)"),
+        (status = 401, description = "Authentication error"),
+        (status = 404, description = "No matching quote found"),
+        (status = 412, description = "Quote changed since the ETag given in If-Match"),
+        (status = 428, description = "If-Match is required and was not sent"),
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote to update"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the quote being replaced")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_quote(
+    claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(quote_id): Path<String>,
+    headers: HeaderMap,
+    Json(mut quote_update): Json<JsonQuote>,
+) -> Response {
+    if let Err(message) = quote_update.clean() {
+        return bad_request(message);
+    }
+    let state_guard = app_state.read().await;
+    let db_pool = &state_guard.db;
+    let expected = match expected_version(&headers, state_guard.require_if_match) {
+        Ok(expected) => expected,
+        Err(e) => return e.into_response(),
+    };
+    match quote::update_quote_in_db(db_pool, &quote_id, quote_update, &claims.sub, expected).await {
+        Ok(()) => {
+            state_guard.reindex(&quote_id).await;
+            get_quote_data_for_api(&state_guard, &quote_id, &[], None, cache::PRIVATE_NO_STORE)
+                .await
+                .into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/quote/{quote_id}",
+    responses(
+        (status = 204, description = "Quote moved to the trash"),
+        (status = 401, description = "Authentication error"),
+        (status = 404, description = "No matching quote found"),
+        (status = 412, description = "Quote changed since the ETag given in If-Match"),
+        (status = 428, description = "If-Match is required and was not sent"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote to delete"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the quote being deleted")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_quote(
+    claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(quote_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, QuoteWriteError> {
+    let state_guard = app_state.read().await;
+    let expected = expected_version(&headers, state_guard.require_if_match)?;
+    quote::delete_quote_from_db(&state_guard.db, &quote_id, &claims.sub, expected).await?;
+    state_guard.reindex(&quote_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/quote/{quote_id}/history",
+    responses(
+        (status = 200, description = "Revisions of a quote, oldest first", body = Vec<QuoteRevision>),
+        (status = 304, description = "History unchanged since the given validators"),
+        (status = 404, description = "No history for this quote id"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote")
+    )
+)]
+pub async fn get_quote_history(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(quote_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let state_guard = app_state.read().await;
+    match revision::get_history_from_db(&state_guard.db, &quote_id).await {
+        Ok(history) if history.is_empty() => Err(StatusCode::NOT_FOUND),
+        Ok(history) => {
+            let last_modified = history.last().map(|rev| rev.created_at);
+            Ok(cache::json_with_validators(
+                Some(&headers),
+                &history,
+                None,
+                last_modified,
+                cache::REVALIDATE,
+            ))
+        }
+        Err(e) => {
+            tracing::error!("API: Failed to load history for {}: {}", quote_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/quote/{quote_id}/revert",
+    request_body = RevertRequest,
+    responses(
+        (status = 200, description = "Quote restored to the given revision", body = JsonQuote),
+        (status = 401, description = "Authentication error"),
+        (status = 404, description = "No such revision"),
+        (status = 412, description = "Quote changed since the ETag given in If-Match"),
+        (status = 428, description = "If-Match is required and was not sent"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote to revert"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the quote being reverted")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn revert_quote(
+    claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(quote_id): Path<String>,
+    headers: HeaderMap,
+    Json(revert): Json<RevertRequest>,
+) -> Result<Response, QuoteWriteError> {
+    let state_guard = app_state.read().await;
+    let db_pool = &state_guard.db;
+    let expected = expected_version(&headers, state_guard.require_if_match)?;
+    quote::revert_quote_in_db(db_pool, &quote_id, revert.revision, &claims.sub, expected).await?;
+    state_guard.reindex(&quote_id).await;
+    Ok(get_quote_data_for_api(&state_guard, &quote_id, &[], None, cache::PRIVATE_NO_STORE)
+        .await
+        .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/quote/{quote_id}/restore",
+    responses(
+        (status = 200, description = "Quote taken out of the trash", body = JsonQuote),
+        (status = 401, description = "Authentication error"),
+        (status = 404, description = "No matching quote in the trash"),
+        (status = 412, description = "Quote changed since the ETag given in If-Match"),
+        (status = 428, description = "If-Match is required and was not sent"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote to restore"),
+        ("If-Match" = Option<String>, Header, description = "ETag of the trashed quote, \"v\" and its version")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn restore_quote(
+    claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(quote_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, QuoteWriteError> {
+    let state_guard = app_state.read().await;
+    let db_pool = &state_guard.db;
+    let expected = expected_version(&headers, state_guard.require_if_match)?;
+    quote::restore_quote_in_db(db_pool, &quote_id, &claims.sub, expected).await?;
+    state_guard.reindex(&quote_id).await;
+    Ok(get_quote_data_for_api(&state_guard, &quote_id, &[], None, cache::PRIVATE_NO_STORE)
+        .await
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CitationParams {
+    #[serde(default)]
+    style: CitationStyle,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/quote/{quote_id}/citation",
+    responses(
+        (status = 200, description = "One citation per source of the quote, separated by blank lines", body = String,
+            content_type = "text/plain"),
+        (status = 400, description = "Unknown citation style"),
+        (status = 404, description = "No matching quote found, or it has no sources"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote to cite"),
+        ("style" = Option<String>, Query, description = "apa, mla or bibtex (default: apa)")
+    )
+)]
+pub async fn get_citations(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(quote_id): Path<String>,
+    Query(params): Query<CitationParams>,
+) -> Response {
+    let state_guard = app_state.read().await;
+    logging::record_quote_id(&quote_id);
+    let quote_obj = match state_guard.quote_index.get_quote(&state_guard.db, &quote_id).await {
+        Ok((quote_obj, _)) => quote_obj,
+        Err(sqlx::Error::RowNotFound) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!("API: quote fetch failed for id {}: {}", quote_id, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let sources = citation::sources_to_cite(&quote_obj);
+    if sources.is_empty() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let body = citation::format_citations(&quote_obj, &sources, params.style);
+    ([(header::CONTENT_TYPE, params.style.content_type())], body).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/quote/{quote_id}/translations",
+    responses(
+        (status = 200, description = "Translations of the quote, by language", body = Vec<QuoteTranslation>),
+        (status = 404, description = "No matching quote found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the translated quote")
+    )
+)]
+pub async fn list_translations(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(quote_id): Path<String>,
+) -> Response {
+    let state_guard = app_state.read().await;
+    match translation::get_translations_from_db(&state_guard.db, &quote_id).await {
+        Ok(translations) => Json(translations).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/quote/{quote_id}/translations/{lang}",
+    request_body = TranslationRequest,
+    responses(
+        (status = 200, description = "Translation added or replaced", body = QuoteTranslation),
+        (status = 400, description = "Invalid language tag or empty text"),
+        (status = 401, description = "Authentication error"),
+        (status = 404, description = "No matching quote found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote to translate"),
+        ("lang" = String, Path, description = "BCP-47 language tag, e.g. es or pt-BR")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn put_translation(
+    claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path((quote_id, lang)): Path<(String, String)>,
+    Json(request): Json<TranslationRequest>,
+) -> Response {
+    let state_guard = app_state.read().await;
+    let db_pool = &state_guard.db;
+    match translation::put_translation_in_db(db_pool, &quote_id, &lang, request, &claims.sub).await {
+        Ok(translated) => Json(translated).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/quote/{quote_id}/translations/{lang}",
+    responses(
+        (status = 204, description = "Translation removed"),
+        (status = 400, description = "Invalid language tag"),
+        (status = 401, description = "Authentication error"),
+        (status = 404, description = "No matching quote or translation found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the translated quote"),
+        ("lang" = String, Path, description = "BCP-47 language tag of the translation")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn delete_translation(
+    claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path((quote_id, lang)): Path<(String, String)>,
+) -> Response {
+    let state_guard = app_state.read().await;
+    match translation::delete_translation_from_db(&state_guard.db, &quote_id, &lang).await {
+        Ok(()) => {
+            tracing::info!("API: {} removed the {} translation of {}", claims.sub, lang, quote_id);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Who a vote through the API counts against. A request carrying an
+/// `Authorization` header must carry a valid token; without one the vote
+/// is anonymous.
+fn api_voter(state: &AppState, client: IpAddr, headers: &HeaderMap) -> Result<String, AuthError> {
+    let subject = match headers.contains_key(header::AUTHORIZATION) {
+        true => Some(
+            authjwt::bearer_subject(headers, &state.jwt_keys).ok_or(AuthError::InvalidToken)?,
+        ),
+        false => None,
+    };
+    Ok(vote::voter(subject.as_deref(), client, &state.jwt_keys.voter))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/quote/{quote_id}/vote",
+    responses(
+        (status = 200, description = "The quote's vote totals and the caller's vote", body = VoteTally),
+        (status = 401, description = "Invalid authentication token"),
+        (status = 404, description = "No matching quote found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote")
+    )
+)]
+pub async fn get_votes(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    ClientIp(client): ClientIp,
+    Path(quote_id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let state_guard = app_state.read().await;
+    let voter = match api_voter(&state_guard, client, &headers) {
+        Ok(voter) => voter,
+        Err(e) => return e.into_response(),
+    };
+    match vote::get_votes_from_db(&state_guard.db, &quote_id, &voter).await {
+        Ok(tally) => Json(tally).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+/// Records or withdraws the caller's vote and refreshes the quote's weight
+/// in the index.
+async fn cast_vote(
+    state: &AppState,
+    client: IpAddr,
+    headers: &HeaderMap,
+    quote_id: &str,
+    direction: Option<VoteDirection>,
+) -> Response {
+    let voter = match api_voter(state, client, headers) {
+        Ok(voter) => voter,
+        Err(e) => return e.into_response(),
+    };
+    match vote::cast_vote_in_db(&state.db, quote_id, &voter, direction).await {
+        Ok(tally) => {
+            state.reindex(quote_id).await;
+            Json(tally).into_response()
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/quote/{quote_id}/vote",
+    request_body = VoteRequest,
+    responses(
+        (status = 200, description = "Vote recorded, replacing the caller's earlier vote", body = VoteTally),
+        (status = 401, description = "Invalid authentication token"),
+        (status = 404, description = "No matching quote found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote to vote on")
+    )
+)]
+pub async fn put_vote(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    ClientIp(client): ClientIp,
+    Path(quote_id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<VoteRequest>,
+) -> Response {
+    let state_guard = app_state.read().await;
+    cast_vote(&state_guard, client, &headers, &quote_id, Some(request.vote)).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/quote/{quote_id}/vote",
+    responses(
+        (status = 200, description = "The caller's vote withdrawn, if there was one", body = VoteTally),
+        (status = 401, description = "Invalid authentication token"),
+        (status = 404, description = "No matching quote found"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("quote_id" = String, Path, description = "ID of the quote")
+    )
+)]
+pub async fn delete_vote(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    ClientIp(client): ClientIp,
+    Path(quote_id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let state_guard = app_state.read().await;
+    cast_vote(&state_guard, client, &headers, &quote_id, None).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/trash",
+    responses(
+        (status = 200, description = "Deleted quotes, most recently deleted first", body = Vec<TrashedQuote>),
+        (status = 401, description = "Authentication error"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_trash(
+    _claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<Vec<TrashedQuote>>, StatusCode> {
+    let state_guard = app_state.read().await;
+    match quote::get_trash_from_db(&state_guard.db).await {
+        Ok(trash) => Ok(Json(trash)),
+        Err(e) => {
+            tracing::error!("API: Failed to list trash: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/broken-links",
+    responses(
+        (status = 200, description = "Source links that failed their last check, longest broken first", body = Vec<BrokenLink>),
+        (status = 401, description = "Authentication error"),
+        (status = 500, description = "Internal server error")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn get_broken_links(
+    _claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+) -> Result<Json<Vec<BrokenLink>>, StatusCode> {
     let state_guard = app_state.read().await;
-    match quote::add_quote_to_db(&state_guard.db, quote_to_add).await {
-        Ok(()) => StatusCode::CREATED.into_response(),
+    match linkcheck::get_broken_links_from_db(&state_guard.db).await {
+        Ok(links) => Ok(Json(links)),
         Err(e) => {
-            tracing::error!("API: Failed to add quote: {}", e);
+            tracing::error!("API: Failed to list broken links: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/submissions",
+    request_body = SubmissionRequest,
+    responses(
+        (status = 202, description = "Submission queued for review"),
+        (status = 400, description = "Submission rejected by the spam checks"),
+        (status = 429, description = "Too many submissions from this address")
+    )
+)]
+pub async fn submit_quote(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    ClientIp(client): ClientIp,
+    Json(request): Json<SubmissionRequest>,
+) -> Response {
+    let state_guard = app_state.read().await;
+    let submitter_ip = client.to_string();
+    let per_hour = state_guard.submissions_per_hour;
+    match submission::submit_quote_to_db(&state_guard.db, request, &submitter_ip, per_hour).await {
+        Ok(()) => (
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "status": "pending" })),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmissionListParams {
+    status: Option<SubmissionStatus>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/submissions",
+    responses(
+        (status = 200, description = "Submissions with the given status, oldest first", body = Vec<Submission>),
+        (status = 401, description = "Authentication error"),
+        (status = 500, description = "Internal server error")
+    ),
+    params(
+        ("status" = Option<SubmissionStatus>, Query, description = "Filter by status (default: pending)")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn list_submissions(
+    _claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Query(params): Query<SubmissionListParams>,
+) -> Result<Json<Vec<Submission>>, StatusCode> {
+    let state_guard = app_state.read().await;
+    let status = params.status.unwrap_or(SubmissionStatus::Pending);
+    match submission::get_submissions_from_db(&state_guard.db, status).await {
+        Ok(submissions) => Ok(Json(submissions)),
+        Err(e) => {
+            tracing::error!("API: Failed to list submissions: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
 
-            (StatusCode::BAD_REQUEST, e.to_string()).into_response()
+#[utoipa::path(
+    put,
+    path = "/api/v1/submissions/{submission_id}",
+    request_body = JsonQuote,
+    responses(
+        (status = 200, description = "Submission updated", body = Submission),
+        (status = 400, description = "Edited quote failed validation"),
+        (status = 401, description = "Authentication error"),
+        (status = 404, description = "No pending submission with that id")
+    ),
+    params(
+        ("submission_id" = i64, Path, description = "ID of the submission")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn update_submission(
+    _claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(submission_id): Path<i64>,
+    Json(quote_edit): Json<JsonQuote>,
+) -> Response {
+    let state_guard = app_state.read().await;
+    match submission::update_submission_in_db(&state_guard.db, submission_id, quote_edit).await {
+        Ok(updated) => Json(updated).into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/submissions/{submission_id}/approve",
+    responses(
+        (status = 201, description = "Submission promoted to a quote", body = JsonQuote),
+        (status = 401, description = "Authentication error"),
+        (status = 404, description = "No pending submission with that id"),
+        (status = 409, description = "A quote with the submitted id already exists, or the quote resembles existing ones")
+    ),
+    params(
+        ("submission_id" = i64, Path, description = "ID of the submission"),
+        ("force" = Option<bool>, Query, description = "Approve the quote even if it resembles existing ones")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn approve_submission(
+    claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(submission_id): Path<i64>,
+    Query(params): Query<ForceParams>,
+) -> Response {
+    let state_guard = app_state.read().await;
+    let approved = submission::approve_submission_in_db(
+        &state_guard.db,
+        submission_id,
+        &claims.sub,
+        &state_guard.duplicates,
+        &state_guard.trigram_index,
+        params.force,
+    )
+    .await;
+    match approved {
+        Ok(approved) => {
+            state_guard.reindex(&approved.id).await;
+            (StatusCode::CREATED, Json(approved)).into_response()
         }
+        Err(e) => e.into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/submissions/{submission_id}/reject",
+    request_body = RejectRequest,
+    responses(
+        (status = 204, description = "Submission rejected"),
+        (status = 401, description = "Authentication error"),
+        (status = 404, description = "No pending submission with that id")
+    ),
+    params(
+        ("submission_id" = i64, Path, description = "ID of the submission")
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn reject_submission(
+    claims: Claims,
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(submission_id): Path<i64>,
+    Json(rejection): Json<RejectRequest>,
+) -> Response {
+    let state_guard = app_state.read().await;
+    let db_pool = &state_guard.db;
+    match submission::reject_submission_in_db(db_pool, submission_id, &claims.sub, &rejection.reason)
+        .await
+    {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => e.into_response(),
     }
 }
@@ -0,0 +1,187 @@
+// assets.rs
+use crate::AppState;
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub const STYLESHEET: &str = "knock.css";
+pub const FAVICON: &str = "favicon.ico";
+
+/// Static files compiled into the binary, with their content types.
+const EMBEDDED: &[(&str, &str, &[u8])] = &[
+    (STYLESHEET, "text/css; charset=utf-8", include_bytes!("../assets/static/knock.css")),
+    (FAVICON, "image/vnd.microsoft.icon", include_bytes!("../assets/static/favicon.ico")),
+];
+
+/// Hashed URLs never change content, so they can be cached for good.
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// For fixed URLs browsers ask for on their own, like /favicon.ico.
+const UNHASHED: &str = "public, max-age=86400";
+
+#[derive(Debug, thiserror::Error)]
+pub enum AssetError {
+    #[error("asset {name} is missing from {dir}: {source}")]
+    Missing {
+        name: &'static str,
+        dir: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("template {template} in theme {theme} links {link}, which is not a registered asset")]
+    Unregistered {
+        theme: String,
+        template: &'static str,
+        link: String,
+    },
+}
+
+struct Asset {
+    content_type: &'static str,
+    body: Bytes,
+    hashed_name: String,
+}
+
+/// The static files served by the web pages, each under a URL containing a
+/// hash of its content.
+pub struct Assets {
+    by_name: HashMap<String, Asset>,
+    by_hashed_name: HashMap<String, String>,
+}
+
+/// `knock.css` with content hash `abcd` becomes `knock.abcd.css`.
+fn hashed_name(name: &str, body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hash: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    match name.rsplit_once('.') {
+        Some((stem, ext)) => format!("{}.{}.{}", stem, hash, ext),
+        None => format!("{}.{}", name, hash),
+    }
+}
+
+impl Assets {
+    /// Uses the embedded copies, or reads every asset from `dir` instead.
+    pub fn load(dir: Option<&FsPath>) -> Result<Self, AssetError> {
+        let mut assets = Self {
+            by_name: HashMap::new(),
+            by_hashed_name: HashMap::new(),
+        };
+        for &(name, content_type, embedded) in EMBEDDED {
+            let body = match dir {
+                Some(dir) => std::fs::read(dir.join(name))
+                    .map(Bytes::from)
+                    .map_err(|source| AssetError::Missing {
+                        name,
+                        dir: dir.to_path_buf(),
+                        source,
+                    })?,
+                None => Bytes::from_static(embedded),
+            };
+            assets.insert(name.to_string(), content_type, body);
+        }
+        Ok(assets)
+    }
+
+    /// Registers an asset loaded at runtime, such as a theme's stylesheet.
+    pub fn insert(&mut self, name: String, content_type: &'static str, body: Bytes) {
+        let hashed_name = hashed_name(&name, &body);
+        if let Some(old) = self.by_name.get(&name) {
+            self.by_hashed_name.remove(&old.hashed_name);
+        }
+        self.by_hashed_name.insert(hashed_name.clone(), name.clone());
+        self.by_name.insert(
+            name,
+            Asset {
+                content_type,
+                body,
+                hashed_name,
+            },
+        );
+    }
+
+    /// Content-hashed URL of a registered asset.
+    pub fn url(&self, name: &str) -> Option<String> {
+        let asset = self.by_name.get(name)?;
+        Some(format!("/static/{}", asset.hashed_name))
+    }
+
+    /// The first `/static/` link in `html` that no registered asset is
+    /// served under.
+    pub fn unregistered_link<'a>(&self, html: &'a str) -> Option<&'a str> {
+        html.match_indices("/static/").find_map(|(start, prefix)| {
+            let rest = &html[start + prefix.len()..];
+            let end = rest
+                .find(|c: char| c == '"' || c == '\'' || c == ')' || c.is_whitespace())
+                .unwrap_or(rest.len());
+            let hashed_name = &rest[..end];
+            (!self.by_hashed_name.contains_key(hashed_name))
+                .then(|| &html[start..start + prefix.len() + end])
+        })
+    }
+
+    fn response(&self, name: &str, cache_control: &'static str) -> Response {
+        match self.by_name.get(name) {
+            Some(asset) => (
+                [
+                    (header::CONTENT_TYPE, asset.content_type),
+                    (header::CACHE_CONTROL, cache_control),
+                ],
+                asset.body.clone(),
+            )
+                .into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+}
+
+pub async fn get_static_asset(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    Path(hashed_name): Path<String>,
+) -> Response {
+    let app_reader = app_state.read().await;
+    let assets = &app_reader.assets;
+    match assets.by_hashed_name.get(&hashed_name) {
+        Some(name) => assets.response(name, IMMUTABLE),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub async fn get_favicon(State(app_state): State<Arc<RwLock<AppState>>>) -> Response {
+    app_state.read().await.assets.response(FAVICON, UNHASHED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registered_links_pass() {
+        let assets = Assets::load(None).unwrap();
+        let html = format!(
+            r#"<link rel="stylesheet" href="{}"><link rel="icon" href="{}">"#,
+            assets.url(STYLESHEET).unwrap(),
+            assets.url(FAVICON).unwrap()
+        );
+        assert_eq!(assets.unregistered_link(&html), None);
+    }
+
+    #[test]
+    fn unregistered_links_are_found() {
+        let assets = Assets::load(None).unwrap();
+        assert_eq!(
+            assets.unregistered_link(r#"<img src="/static/logo.png" alt="">"#),
+            Some("/static/logo.png")
+        );
+        assert_eq!(
+            assets.unregistered_link("body { background: url(/static/knock.css) }"),
+            Some("/static/knock.css")
+        );
+        assert_eq!(assets.url("logo.png"), None);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::AppState;
 use axum::{
-    extract::{FromRequestParts, FromRef}, http::{request::Parts, StatusCode}, response::{IntoResponse, Json, Response}, RequestPartsExt,
+    extract::{FromRequestParts, FromRef}, http::{header, request::Parts, HeaderMap, StatusCode}, response::{IntoResponse, Json, Response}, RequestPartsExt,
 };
 
 use axum_extra::{
@@ -10,6 +10,7 @@ use axum_extra::{
 use chrono::{TimeDelta, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use utoipa::ToSchema;
@@ -17,13 +18,20 @@ use utoipa::ToSchema;
 
 pub struct JwtKeys {
     pub encoding: EncodingKey, pub decoding: DecodingKey,
+    /// Keys anonymous voter ids, derived from the JWT secret so it needs no
+    /// file of its own.
+    pub voter: [u8; 32],
 }
 
 impl JwtKeys {
     pub fn new(secret: &[u8]) -> Self {
+        let mut voter = Sha256::new();
+        voter.update(b"quote_server voter key\n");
+        voter.update(secret);
         Self {
             encoding: EncodingKey::from_secret(secret),
             decoding: DecodingKey::from_secret(secret),
+            voter: voter.finalize().into(),
         }
 
 
@@ -99,6 +107,12 @@ pub enum AuthError {
 
 impl IntoResponse for AuthError {
     fn into_response(self) -> Response {
+        let reason = match self {
+            AuthError::InvalidToken => "invalid_token",
+            AuthError::TokenCreation => "token_creation",
+            AuthError::InvalidRegistrationKey => "invalid_registration_key",
+        };
+        metrics::counter!("auth_failures_total", "reason" => reason).increment(1);
         let (status, error_message) = match self {
             AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid authentication token."),
             AuthError::TokenCreation => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error creating token."),
@@ -130,18 +144,43 @@ where
         let app_state = Arc::<RwLock<AppState>>::from_ref(state);
 
         let app_state_reader = app_state.read().await;
-        let decoding_key = &app_state_reader.jwt_keys.decoding;
-        let validation = Validation::new(jsonwebtoken::Algorithm::HS512);
+        decode_claims(bearer.token(), &app_state_reader.jwt_keys)
+    }
 
-        let token_data = decode::<Claims>(bearer.token(), decoding_key, &validation)
-            .map_err(|_| AuthError::InvalidToken)?;
 
-        Ok(token_data.claims)
-    }
+}
+
 
+pub fn decode_claims(token: &str, jwt_keys: &JwtKeys) -> Result<Claims, AuthError> {
+    let validation = Validation::new(jsonwebtoken::Algorithm::HS512);
+    let token_data = decode::<Claims>(token, &jwt_keys.decoding, &validation)
+        .map_err(|_| AuthError::InvalidToken)?;
 
+    Ok(token_data.claims)
 }
 
+/// Whether `jwt_keys` can sign a token and verify it again.
+pub fn keys_usable(jwt_keys: &JwtKeys) -> bool {
+    let claims = Claims {
+        iss: "quote-server.example.com".to_owned(),
+        sub: "readiness-probe".to_owned(),
+        exp: (Utc::now() + TimeDelta::minutes(1)).timestamp(),
+    };
+    let header = Header::new(jsonwebtoken::Algorithm::HS512);
+    encode(&header, &claims, &jwt_keys.encoding)
+        .is_ok_and(|token| decode_claims(&token, jwt_keys).is_ok())
+}
+
+/// Subject of a valid bearer token in `headers`, for middleware that runs
+/// before the `Claims` extractor.
+pub fn bearer_subject(headers: &HeaderMap, jwt_keys: &JwtKeys) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| decode_claims(token, jwt_keys).ok())
+        .map(|claims| claims.sub)
+}
 
 pub fn register_and_create_token(
     app_state: &AppState,
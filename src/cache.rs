@@ -0,0 +1,130 @@
+// cache.rs
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+pub const NO_STORE: &str = "no-store";
+pub const PRIVATE_NO_STORE: &str = "private, no-store";
+pub const REVALIDATE: &str = "no-cache";
+
+/// Strong entity tag over the exact bytes of a representation. Versioned
+/// resources carry their version in front so `If-Match` can be checked
+/// against the database without re-rendering.
+pub fn etag_for(body: &[u8], version: Option<i64>) -> String {
+    let digest = format!("{:x}", Sha256::digest(body));
+    match version {
+        Some(version) => format!("\"v{}-{}\"", version, &digest[..32]),
+        None => format!("\"{}\"", &digest[..32]),
+    }
+}
+
+/// What an `If-Match` header asks of a versioned resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IfMatch {
+    Absent,
+    Any,
+    Version(i64),
+    /// Present but names no version we could have issued.
+    Unmatchable,
+}
+
+/// Reads the version out of `If-Match`. Weak tags never match, per the
+/// strong comparison `If-Match` requires.
+pub fn if_match(request_headers: &HeaderMap) -> IfMatch {
+    let Some(value) = request_headers.get(header::IF_MATCH) else {
+        return IfMatch::Absent;
+    };
+    let Ok(value) = value.to_str() else {
+        return IfMatch::Unmatchable;
+    };
+    if value.trim() == "*" {
+        return IfMatch::Any;
+    }
+    value
+        .split(',')
+        .map(str::trim)
+        .filter_map(|tag| tag.strip_prefix("\"")?.strip_suffix('"')?.strip_prefix('v'))
+        .find_map(|tag| tag.split('-').next()?.parse().ok())
+        .map_or(IfMatch::Unmatchable, IfMatch::Version)
+}
+
+fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Weak comparison as required for `If-None-Match`.
+fn etag_matches(header_value: &str, etag: &str) -> bool {
+    header_value.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.trim_start_matches("W/") == etag.trim_start_matches("W/")
+    })
+}
+
+fn not_modified(
+    request_headers: &HeaderMap,
+    etag: &str,
+    last_modified: Option<DateTime<Utc>>,
+) -> bool {
+    if let Some(inm) = request_headers.get(header::IF_NONE_MATCH) {
+        return inm.to_str().is_ok_and(|value| etag_matches(value, etag));
+    }
+    let since = request_headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+    match (since, last_modified) {
+        (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+/// Serializes `value` as JSON with an `ETag`, optional `Last-Modified` and
+/// the given `Cache-Control`. When `request_headers` are passed, matching
+/// `If-None-Match`/`If-Modified-Since` validators produce a 304 instead.
+pub fn json_with_validators<T: Serialize>(
+    request_headers: Option<&HeaderMap>,
+    value: &T,
+    version: Option<i64>,
+    last_modified: Option<DateTime<Utc>>,
+    cache_control: &str,
+) -> Response {
+    let body = match serde_json::to_vec(value) {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Failed to serialize response: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let etag = etag_for(&body, version);
+
+    let is_not_modified =
+        request_headers.is_some_and(|headers| not_modified(headers, &etag, last_modified));
+    let mut response = if is_not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let mut response = Response::new(Body::from(body));
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        response
+    };
+
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Some(modified) = last_modified {
+        if let Ok(value) = HeaderValue::from_str(&http_date(modified)) {
+            headers.insert(header::LAST_MODIFIED, value);
+        }
+    }
+    if let Ok(value) = HeaderValue::from_str(cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+    response
+}
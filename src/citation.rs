@@ -0,0 +1,581 @@
+// citation.rs
+use crate::quote::Quote;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+use std::fmt::Write;
+use utoipa::ToSchema;
+
+/// Whether a source carries the speaker's own words (a transcript, their
+/// own post) or reports them.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum SourceKind {
+    Primary,
+    #[default]
+    Secondary,
+}
+
+/// A work a quote is cited from.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct QuoteSource {
+    #[schema(example = "https://www.nba.com/news/kevin-garnett-anything-is-possible")]
+    pub url: String,
+    pub title: Option<String>,
+    pub publisher: Option<String>,
+    /// When the source was last seen at `url`.
+    pub accessed_on: Option<NaiveDate>,
+    #[serde(default)]
+    pub kind: SourceKind,
+}
+
+impl QuoteSource {
+    /// Trims the text fields, dropping blank ones, and checks the URL.
+    pub fn clean(&mut self) -> Result<(), &'static str> {
+        self.url = self.url.trim().to_string();
+        for value in [&mut self.title, &mut self.publisher] {
+            *value = value
+                .take()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty());
+        }
+        let scheme_ok = ["http://", "https://"].iter().any(|scheme| {
+            self.url
+                .get(..scheme.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        });
+        if !scheme_ok
+            || self.url.len() <= "https://".len()
+            || self.url.contains(char::is_whitespace)
+        {
+            return Err("source urls must be absolute http(s) URLs");
+        }
+        Ok(())
+    }
+}
+
+/// The sources of a quote to cite: its own list, or else its single
+/// `source` link as a secondary source.
+pub fn sources_to_cite(quote: &Quote) -> Vec<QuoteSource> {
+    if !quote.sources.is_empty() || quote.source.trim().is_empty() {
+        return quote.sources.clone();
+    }
+    vec![QuoteSource {
+        url: quote.source.trim().to_string(),
+        title: None,
+        publisher: None,
+        accessed_on: None,
+        kind: SourceKind::Secondary,
+    }]
+}
+
+/// Replaces the sources of a quote, keeping their order.
+pub async fn replace_sources(
+    conn: &mut SqliteConnection,
+    quote_id: &str,
+    sources: &[QuoteSource],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM quote_sources WHERE quote_id = $1", quote_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for (position, source) in sources.iter().enumerate() {
+        let position = position as i64;
+        sqlx::query!(
+            "INSERT INTO quote_sources (quote_id, position, url, title, publisher, accessed_on, kind)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            quote_id,
+            position,
+            source.url,
+            source.title,
+            source.publisher,
+            source.accessed_on,
+            source.kind,
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CitationStyle {
+    #[default]
+    Apa,
+    Mla,
+    Bibtex,
+}
+
+impl CitationStyle {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            CitationStyle::Apa | CitationStyle::Mla => "text/plain; charset=utf-8",
+            CitationStyle::Bibtex => "application/x-bibtex; charset=utf-8",
+        }
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+/// MLA abbreviates months of more than four letters.
+const MLA_MONTHS: [&str; 12] = [
+    "Jan.", "Feb.", "Mar.", "Apr.", "May", "June", "July", "Aug.", "Sept.", "Oct.", "Nov.", "Dec.",
+];
+const BIBTEX_MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const NAME_SUFFIXES: [&str; 6] = ["Jr.", "Jr", "Sr.", "Sr", "II", "III"];
+
+/// A speaker's name split into given names, family name and suffix.
+struct Name<'a> {
+    given: Vec<&'a str>,
+    family: &'a str,
+    suffix: Option<&'a str>,
+}
+
+impl<'a> Name<'a> {
+    fn parse(full: &'a str) -> Self {
+        let mut parts: Vec<&str> = full.split_whitespace().collect();
+        let suffix = match parts.last() {
+            Some(last) if parts.len() > 2 && NAME_SUFFIXES.contains(last) => parts.pop(),
+            _ => None,
+        };
+        let family = parts.pop().unwrap_or_default();
+        Self {
+            given: parts,
+            family,
+            suffix,
+        }
+    }
+
+    /// `Family, G., Suffix`
+    fn initials(&self) -> String {
+        let initials: Vec<String> = self
+            .given
+            .iter()
+            .filter_map(|given| given.chars().next())
+            .map(|initial| format!("{}.", initial))
+            .collect();
+        self.inverted(&initials.join(" "))
+    }
+
+    /// `Family, Given, Suffix`
+    fn full(&self) -> String {
+        self.inverted(&self.given.join(" "))
+    }
+
+    fn inverted(&self, given: &str) -> String {
+        let mut name = self.family.to_string();
+        if !given.is_empty() {
+            name.push_str(", ");
+            name.push_str(given);
+        }
+        if let Some(suffix) = self.suffix {
+            name.push_str(", ");
+            name.push_str(suffix);
+        }
+        name
+    }
+}
+
+/// Year, month and day of a partial ISO date, as far as it goes.
+fn date_parts(date: Option<&str>) -> (Option<i32>, Option<usize>, Option<u32>) {
+    let Some(date) = date else {
+        return (None, None, None);
+    };
+    let mut parts = date.split('-');
+    let year = parts.next().and_then(|year| year.parse().ok());
+    let month = parts
+        .next()
+        .and_then(|month| month.parse::<usize>().ok())
+        .filter(|month| (1..=12).contains(month));
+    let day = parts.next().and_then(|day| day.parse().ok());
+    (year, month, day)
+}
+
+/// Ends `text` with a period unless it already ends in punctuation.
+fn sentence(text: &str) -> String {
+    if text.ends_with(['.', '?', '!']) {
+        text.to_string()
+    } else {
+        format!("{}.", text)
+    }
+}
+
+/// Renders each source of `quote` in `style`, one citation per paragraph.
+/// The quote's `said_on` stands in for the publication date; primary
+/// sources are credited to the speaker.
+pub fn format_citations(quote: &Quote, sources: &[QuoteSource], style: CitationStyle) -> String {
+    let citations: Vec<String> = sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| match style {
+            CitationStyle::Apa => apa(quote, source),
+            CitationStyle::Mla => mla(quote, source),
+            CitationStyle::Bibtex => bibtex(quote, source, i + 1),
+        })
+        .collect();
+    let mut text = citations.join("\n\n");
+    text.push('\n');
+    text
+}
+
+/// What to call an untitled source.
+fn description(quote: &Quote) -> String {
+    match &quote.attribution.event {
+        Some(event) => event.clone(),
+        None => format!("Remarks by {}", quote.whos_there.trim()),
+    }
+}
+
+fn apa(quote: &Quote, source: &QuoteSource) -> String {
+    let date = match date_parts(quote.attribution.said_on.as_deref()) {
+        (Some(year), Some(month), Some(day)) => {
+            format!("({}, {} {})", year, MONTHS[month - 1], day)
+        }
+        (Some(year), Some(month), None) => format!("({}, {})", year, MONTHS[month - 1]),
+        (Some(year), _, _) => format!("({})", year),
+        _ => "(n.d.)".to_string(),
+    };
+    let title = match &source.title {
+        Some(title) => sentence(title),
+        None => format!("[{}].", description(quote)),
+    };
+
+    // Without an author, the title moves into the author position.
+    let mut citation = match source.kind {
+        SourceKind::Primary => {
+            let author = sentence(&Name::parse(&quote.whos_there).initials());
+            format!("{} {}. {}", author, date, title)
+        }
+        SourceKind::Secondary => format!("{} {}.", title, date),
+    };
+    if let Some(publisher) = &source.publisher {
+        let _ = write!(citation, " {}", sentence(publisher));
+    }
+    match source.accessed_on {
+        Some(accessed) => {
+            let _ = write!(
+                citation,
+                " Retrieved {} {}, {}, from {}",
+                MONTHS[accessed.month0() as usize],
+                accessed.day(),
+                accessed.year(),
+                source.url
+            );
+        }
+        None => {
+            let _ = write!(citation, " {}", source.url);
+        }
+    }
+    citation
+}
+
+fn mla(quote: &Quote, source: &QuoteSource) -> String {
+    let mut citation = String::new();
+    if source.kind == SourceKind::Primary {
+        let _ = write!(
+            citation,
+            "{} ",
+            sentence(&Name::parse(&quote.whos_there).full())
+        );
+    }
+    match &source.title {
+        Some(title) => {
+            let _ = write!(citation, "\u{201c}{}\u{201d}", sentence(title));
+        }
+        None => citation.push_str(&sentence(&description(quote))),
+    }
+
+    // The container, date and location are one comma-separated element.
+    let mut element = Vec::new();
+    if let Some(publisher) = &source.publisher {
+        element.push(publisher.clone());
+    }
+    match date_parts(quote.attribution.said_on.as_deref()) {
+        (Some(year), Some(month), Some(day)) => {
+            element.push(format!("{} {} {}", day, MLA_MONTHS[month - 1], year))
+        }
+        (Some(year), Some(month), None) => {
+            element.push(format!("{} {}", MLA_MONTHS[month - 1], year))
+        }
+        (Some(year), _, _) => element.push(year.to_string()),
+        _ => {}
+    }
+    let location = source
+        .url
+        .split_once("://")
+        .map_or(source.url.as_str(), |(_, rest)| rest);
+    element.push(location.to_string());
+    let _ = write!(citation, " {}.", element.join(", "));
+
+    if let Some(accessed) = source.accessed_on {
+        let _ = write!(
+            citation,
+            " Accessed {} {} {}.",
+            accessed.day(),
+            MLA_MONTHS[accessed.month0() as usize],
+            accessed.year()
+        );
+    }
+    citation
+}
+
+/// Escapes the characters BibTeX treats specially in field values.
+fn bibtex_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '{' | '}' | '%' | '&' | '#' | '$' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn bibtex(quote: &Quote, source: &QuoteSource, number: usize) -> String {
+    let key: String = quote
+        .id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut fields: Vec<(&str, String)> = Vec::new();
+    if source.kind == SourceKind::Primary {
+        let author = Name::parse(&quote.whos_there).full();
+        fields.push(("author", format!("{{{}}}", bibtex_escape(&author))));
+    }
+    let title = source.title.clone().unwrap_or_else(|| description(quote));
+    fields.push(("title", format!("{{{}}}", bibtex_escape(&title))));
+    if let Some(publisher) = &source.publisher {
+        fields.push(("publisher", format!("{{{}}}", bibtex_escape(publisher))));
+    }
+    let (year, month, _) = date_parts(quote.attribution.said_on.as_deref());
+    if let Some(year) = year {
+        fields.push(("year", format!("{{{}}}", year)));
+    }
+    if let Some(month) = month {
+        fields.push(("month", BIBTEX_MONTHS[month - 1].to_string()));
+    }
+    if let Some(said_on) = &quote.attribution.said_on {
+        fields.push(("date", format!("{{{}}}", said_on)));
+    }
+    let url = source.url.replace('%', "\\%");
+    fields.push(("url", format!("{{{}}}", url)));
+    if let Some(accessed) = source.accessed_on {
+        fields.push(("urldate", format!("{{{}}}", accessed)));
+    }
+
+    let mut entry = format!("@misc{{{}-{},\n", key, number);
+    for (name, value) in fields {
+        let _ = writeln!(entry, "  {} = {},", name, value);
+    }
+    entry.push('}');
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quote::Attribution;
+
+    fn quote(speaker: &str, said_on: Option<&str>, event: Option<&str>) -> Quote {
+        Quote {
+            id: "kg-1".to_string(),
+            whos_there: speaker.to_string(),
+            answer_who: "Anything is possible!".to_string(),
+            source: String::new(),
+            attribution: Attribution {
+                said_on: said_on.map(str::to_string),
+                event: event.map(str::to_string),
+                ..Attribution::default()
+            },
+            sources: Vec::new(),
+            created_at: None,
+            updated_at: None,
+            created_by: None,
+            updated_by: None,
+            version: 1,
+        }
+    }
+
+    fn source(
+        url: &str,
+        title: Option<&str>,
+        publisher: Option<&str>,
+        kind: SourceKind,
+    ) -> QuoteSource {
+        QuoteSource {
+            url: url.to_string(),
+            title: title.map(str::to_string),
+            publisher: publisher.map(str::to_string),
+            accessed_on: None,
+            kind,
+        }
+    }
+
+    #[test]
+    fn apa_secondary_with_year_and_month() {
+        let quote = quote("Kevin Garnett", Some("2008-06"), None);
+        let source = source(
+            "https://nba.com/kg",
+            Some("Anything is possible"),
+            Some("NBA"),
+            SourceKind::Secondary,
+        );
+        assert_eq!(
+            apa(&quote, &source),
+            "Anything is possible. (2008, June). NBA. https://nba.com/kg"
+        );
+    }
+
+    #[test]
+    fn apa_primary_untitled_with_suffix() {
+        let quote = quote("Ken Griffey Jr.", Some("1997"), None);
+        let mut source = source("https://example.com/x", None, None, SourceKind::Primary);
+        source.accessed_on = NaiveDate::from_ymd_opt(2024, 3, 5);
+        assert_eq!(
+            apa(&quote, &source),
+            "Griffey, K., Jr. (1997). [Remarks by Ken Griffey Jr.]. \
+             Retrieved March 5, 2024, from https://example.com/x"
+        );
+    }
+
+    #[test]
+    fn apa_without_date() {
+        let quote = quote("Kevin Garnett", None, Some("Postgame interview"));
+        let source = source("https://nba.com/kg", None, None, SourceKind::Secondary);
+        assert_eq!(
+            apa(&quote, &source),
+            "[Postgame interview]. (n.d.). https://nba.com/kg"
+        );
+    }
+
+    #[test]
+    fn mla_primary_with_full_date() {
+        let quote = quote("Kevin Garnett", Some("2008-06-17"), None);
+        let mut source = source(
+            "https://www.nba.com/kg",
+            Some("Anything Is Possible"),
+            Some("NBA"),
+            SourceKind::Primary,
+        );
+        source.accessed_on = NaiveDate::from_ymd_opt(2024, 9, 1);
+        assert_eq!(
+            mla(&quote, &source),
+            "Garnett, Kevin. \u{201c}Anything Is Possible.\u{201d} NBA, 17 June 2008, \
+             www.nba.com/kg. Accessed 1 Sept. 2024."
+        );
+    }
+
+    #[test]
+    fn mla_untitled_uses_the_event() {
+        let quote = quote(
+            "Kevin Garnett",
+            Some("2008-06"),
+            Some("2008 Finals postgame"),
+        );
+        let source = source("http://a.example/c", None, None, SourceKind::Secondary);
+        assert_eq!(
+            mla(&quote, &source),
+            "2008 Finals postgame. June 2008, a.example/c."
+        );
+    }
+
+    #[test]
+    fn mla_keeps_suffix_after_given_name() {
+        let quote = quote("Ken Griffey Jr.", None, None);
+        let source = source(
+            "https://a.example/",
+            Some("Swing"),
+            None,
+            SourceKind::Primary,
+        );
+        assert!(mla(&quote, &source).starts_with("Griffey, Ken, Jr. \u{201c}Swing.\u{201d}"));
+    }
+
+    #[test]
+    fn bibtex_escapes_special_characters() {
+        let mut quote = quote("Ken Griffey Jr.", Some("2008-06"), None);
+        quote.id = "kg 1".to_string();
+        let source = source(
+            "https://x.example/a%20b",
+            Some("50% of R&D_{x}"),
+            Some("A#B"),
+            SourceKind::Primary,
+        );
+        assert_eq!(
+            bibtex(&quote, &source, 2),
+            "@misc{kg_1-2,\n\
+             \x20 author = {Griffey, Ken, Jr.},\n\
+             \x20 title = {50\\% of R\\&D\\_\\{x\\}},\n\
+             \x20 publisher = {A\\#B},\n\
+             \x20 year = {2008},\n\
+             \x20 month = jun,\n\
+             \x20 date = {2008-06},\n\
+             \x20 url = {https://x.example/a\\%20b},\n\
+             }"
+        );
+    }
+
+    #[test]
+    fn bibtex_untitled_without_date() {
+        let quote = quote("Kevin Garnett", None, None);
+        let source = source("https://nba.com/kg", None, None, SourceKind::Secondary);
+        assert_eq!(
+            bibtex(&quote, &source, 1),
+            "@misc{kg-1-1,\n  title = {Remarks by Kevin Garnett},\n  url = {https://nba.com/kg},\n}"
+        );
+    }
+
+    #[test]
+    fn citations_are_separated_by_blank_lines() {
+        let quote = quote("Kevin Garnett", Some("2008"), None);
+        let sources = [
+            source(
+                "https://a.example/",
+                Some("One"),
+                None,
+                SourceKind::Secondary,
+            ),
+            source(
+                "https://b.example/",
+                Some("Two"),
+                None,
+                SourceKind::Secondary,
+            ),
+        ];
+        assert_eq!(
+            format_citations(&quote, &sources, CitationStyle::Apa),
+            "One. (2008). https://a.example/\n\nTwo. (2008). https://b.example/\n"
+        );
+    }
+}
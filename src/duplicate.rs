@@ -0,0 +1,356 @@
+// duplicate.rs
+use crate::metrics::QueryTimer;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::RwLock;
+use utoipa::ToSchema;
+
+/// What adding a quote that resembles an existing one does.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateAction {
+    /// Refuse it unless the request forces it.
+    Reject,
+    /// Add it and log a warning.
+    Warn,
+    /// Do not look for duplicates.
+    Off,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DuplicatePolicy {
+    pub action: DuplicateAction,
+    /// Trigram similarity from which two quotes count as duplicates.
+    pub threshold: f64,
+}
+
+/// Parses a similarity threshold, which must lie between 0 and 1.
+pub fn parse_threshold(value: &str) -> Result<f64, String> {
+    let threshold: f64 = value.parse().map_err(|e| format!("{e}"))?;
+    if !(0.0..=1.0).contains(&threshold) {
+        return Err(format!("{threshold} is not between 0 and 1"));
+    }
+    Ok(threshold)
+}
+
+/// An existing quote that resembles the one being written.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DuplicateMatch {
+    pub id: String,
+    /// Trigram similarity of the normalized texts, 1.0 for the same text.
+    #[schema(example = 0.92)]
+    pub similarity: f64,
+}
+
+/// Quotes that all resemble one another, directly or through each other.
+#[derive(Debug)]
+pub struct DuplicateCluster {
+    /// Ids, speakers and texts, by id.
+    pub quotes: Vec<(String, String, String)>,
+    pub min_similarity: f64,
+    pub max_similarity: f64,
+}
+
+/// Lowercases quote text, folds typographic quotes into ASCII ones, drops
+/// apostrophes (so "don't", "don’t" and "dont" agree) and turns every
+/// other run of punctuation and whitespace into a single space.
+pub fn normalize_text(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut gap = false;
+    for c in text.chars().flat_map(char::to_lowercase) {
+        let c = match c {
+            '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' | '\u{2032}' | '`' | '\u{b4}' => '\'',
+            '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' | '\u{2033}' | '\u{ab}'
+            | '\u{bb}' => '"',
+            c => c,
+        };
+        if c.is_alphanumeric() {
+            if gap && !normalized.is_empty() {
+                normalized.push(' ');
+            }
+            gap = false;
+            normalized.push(c);
+        } else if c != '\'' {
+            gap = true;
+        }
+    }
+    normalized
+}
+
+/// Hash of the normalized text. Quotes with equal fingerprints say the same
+/// thing up to case, punctuation and spacing.
+pub fn fingerprint(text: &str) -> String {
+    let digest = format!("{:x}", Sha256::digest(normalize_text(text).as_bytes()));
+    digest[..32].to_string()
+}
+
+type Trigrams = HashSet<[char; 3]>;
+
+fn trigrams(normalized: &str) -> Trigrams {
+    let padded: Vec<char> = format!(" {} ", normalized).chars().collect();
+    padded
+        .windows(3)
+        .map(|window| [window[0], window[1], window[2]])
+        .collect()
+}
+
+/// Jaccard similarity of two trigram sets.
+fn jaccard(a: &Trigrams, b: &Trigrams) -> f64 {
+    let shared = a.intersection(b).count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 {
+        return 0.0;
+    }
+    shared as f64 / total as f64
+}
+
+/// Trigram sets of live quote texts, so checking a new quote for near
+/// duplicates does not re-read and re-split every quote. Kept in step with
+/// SQLite by calling [`TrigramIndex::reload`] after every write, like the
+/// quote index. Empty when duplicate checks are off.
+#[derive(Default)]
+pub struct TrigramIndex {
+    quotes: Option<RwLock<HashMap<String, Trigrams>>>,
+}
+
+impl TrigramIndex {
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "load_trigrams"))]
+    pub async fn load(db: &SqlitePool, enabled: bool) -> Result<Self, sqlx::Error> {
+        if !enabled {
+            return Ok(Self::default());
+        }
+        let _timer = QueryTimer::start("load_trigrams");
+        let rows = sqlx::query!("SELECT id, answer_who FROM quotes WHERE deleted_at IS NULL")
+            .fetch_all(db)
+            .await?;
+        let quotes = rows
+            .into_iter()
+            .map(|row| (row.id, trigrams(&normalize_text(&row.answer_who))))
+            .collect();
+        Ok(Self {
+            quotes: Some(RwLock::new(quotes)),
+        })
+    }
+
+    /// Re-reads `quote_id` after a write, dropping it if it is gone or in
+    /// the trash.
+    #[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "reload_trigrams"))]
+    pub async fn reload(&self, db: &SqlitePool, quote_id: &str) -> Result<(), sqlx::Error> {
+        let Some(quotes) = &self.quotes else {
+            return Ok(());
+        };
+        let _timer = QueryTimer::start("reload_trigrams");
+        let text = sqlx::query_scalar!(
+            "SELECT answer_who FROM quotes WHERE id = $1 AND deleted_at IS NULL",
+            quote_id
+        )
+        .fetch_optional(db)
+        .await?;
+        let mut quotes = quotes.write().unwrap();
+        match text {
+            Some(text) => quotes.insert(quote_id.to_string(), trigrams(&normalize_text(&text))),
+            None => quotes.remove(quote_id),
+        };
+        Ok(())
+    }
+}
+
+/// Live quotes whose text is at least `threshold` similar to `text`, most
+/// similar first. Exact matches come from the fingerprint index, near ones
+/// from `known`.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "find_duplicates"))]
+pub async fn find_duplicates(
+    conn: &mut SqliteConnection,
+    known: &TrigramIndex,
+    text: &str,
+    threshold: f64,
+) -> Result<Vec<DuplicateMatch>, sqlx::Error> {
+    let _timer = QueryTimer::start("find_duplicates");
+    let quote_fingerprint = fingerprint(text);
+    let same: HashSet<String> = sqlx::query_scalar!(
+        "SELECT id FROM quotes WHERE fingerprint = $1 AND deleted_at IS NULL",
+        quote_fingerprint
+    )
+    .fetch_all(conn)
+    .await?
+    .into_iter()
+    .collect();
+
+    let mut matches: Vec<DuplicateMatch> = same
+        .iter()
+        .map(|id| DuplicateMatch {
+            id: id.clone(),
+            similarity: 1.0,
+        })
+        .collect();
+    if let Some(quotes) = &known.quotes {
+        let wanted = trigrams(&normalize_text(text));
+        let quotes = quotes.read().unwrap();
+        matches.extend(quotes.iter().filter_map(|(id, grams)| {
+            if same.contains(id) {
+                return None;
+            }
+            let similarity = jaccard(&wanted, grams);
+            (similarity >= threshold).then(|| DuplicateMatch {
+                id: id.clone(),
+                similarity,
+            })
+        }));
+    }
+    matches.sort_by(|a, b| b.similarity.total_cmp(&a.similarity).then_with(|| a.id.cmp(&b.id)));
+    Ok(matches)
+}
+
+/// Fingerprints quotes written before fingerprints were stored.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "backfill_fingerprints"))]
+pub async fn backfill_fingerprints(db: &SqlitePool) -> Result<usize, sqlx::Error> {
+    let _timer = QueryTimer::start("backfill_fingerprints");
+    let mut tx = db.begin().await?;
+    let rows = sqlx::query!("SELECT id, answer_who FROM quotes WHERE fingerprint IS NULL")
+        .fetch_all(&mut *tx)
+        .await?;
+    for row in &rows {
+        let quote_fingerprint = fingerprint(&row.answer_who);
+        sqlx::query!(
+            "UPDATE quotes SET fingerprint = $2 WHERE id = $1",
+            row.id,
+            quote_fingerprint
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(rows.len())
+}
+
+/// Groups live quotes that are at least `threshold` similar into clusters,
+/// largest first. Compares every pair, so it is meant for offline reports.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "duplicate_clusters"))]
+pub async fn duplicate_clusters(
+    db: &SqlitePool,
+    threshold: f64,
+) -> Result<Vec<DuplicateCluster>, sqlx::Error> {
+    let _timer = QueryTimer::start("duplicate_clusters");
+    let rows = sqlx::query!(
+        "SELECT id, whos_there, answer_who, fingerprint FROM quotes WHERE deleted_at IS NULL ORDER BY id"
+    )
+    .fetch_all(db)
+    .await?;
+    let grams: Vec<_> = rows
+        .iter()
+        .map(|row| trigrams(&normalize_text(&row.answer_who)))
+        .collect();
+
+    // Union-find over the quotes, joining every pair above the threshold.
+    let mut parent: Vec<usize> = (0..rows.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut edges = Vec::new();
+    for i in 0..rows.len() {
+        for j in i + 1..rows.len() {
+            let same = rows[i].fingerprint.is_some() && rows[i].fingerprint == rows[j].fingerprint;
+            let similarity = if same {
+                1.0
+            } else {
+                jaccard(&grams[i], &grams[j])
+            };
+            if similarity >= threshold {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[b] = a;
+                edges.push((i, similarity));
+            }
+        }
+    }
+
+    let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..rows.len() {
+        let cluster = root(&mut parent, i);
+        members.entry(cluster).or_default().push(i);
+    }
+    let mut similarities: BTreeMap<usize, (f64, f64)> = BTreeMap::new();
+    for (i, similarity) in edges {
+        let range = similarities
+            .entry(root(&mut parent, i))
+            .or_insert((similarity, similarity));
+        range.0 = range.0.min(similarity);
+        range.1 = range.1.max(similarity);
+    }
+
+    let mut clusters: Vec<DuplicateCluster> = members
+        .into_iter()
+        .filter(|(_, quotes)| quotes.len() > 1)
+        .map(|(cluster, quotes)| {
+            let (min_similarity, max_similarity) = similarities[&cluster];
+            DuplicateCluster {
+                quotes: quotes
+                    .into_iter()
+                    .map(|i| {
+                        let row = &rows[i];
+                        (
+                            row.id.clone(),
+                            row.whos_there.clone(),
+                            row.answer_who.clone(),
+                        )
+                    })
+                    .collect(),
+                min_similarity,
+                max_similarity,
+            }
+        })
+        .collect();
+    clusters.sort_by_key(|cluster| Reverse(cluster.quotes.len()));
+    Ok(clusters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn similarity(a: &str, b: &str) -> f64 {
+        jaccard(&trigrams(&normalize_text(a)), &trigrams(&normalize_text(b)))
+    }
+
+    #[test]
+    fn typographic_apostrophes_fold_away() {
+        assert_eq!(normalize_text("Don\u{2019}t"), "dont");
+        assert_eq!(normalize_text("don't"), "dont");
+        assert_eq!(normalize_text("dont"), "dont");
+        assert_eq!(fingerprint("I don\u{2019}t know."), fingerprint("i dont know"));
+    }
+
+    #[test]
+    fn punctuation_and_spacing_collapse() {
+        assert_eq!(
+            normalize_text("  \u{201c}Hello,\tworld!\u{201d} -- she said.  "),
+            "hello world she said"
+        );
+        assert_eq!(normalize_text("?!..."), "");
+    }
+
+    #[test]
+    fn jaccard_bounds() {
+        assert_eq!(similarity("Knock knock", "knock, KNOCK!"), 1.0);
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+        assert_eq!(similarity("", ""), 0.0);
+        let near = similarity("Who's there? Lettuce.", "Who is there? Lettuce!");
+        assert!(near > 0.5 && near < 1.0, "{}", near);
+    }
+
+    #[test]
+    fn thresholds_lie_between_zero_and_one() {
+        assert_eq!(parse_threshold("0"), Ok(0.0));
+        assert_eq!(parse_threshold("1"), Ok(1.0));
+        assert_eq!(parse_threshold("0.8"), Ok(0.8));
+        assert!(parse_threshold("1.5").is_err());
+        assert!(parse_threshold("-0.1").is_err());
+        assert!(parse_threshold("NaN").is_err());
+        assert!(parse_threshold("high").is_err());
+    }
+}
@@ -1,4 +1,9 @@
 // error.rs
+use crate::duplicate::DuplicateMatch;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -15,3 +20,91 @@ pub enum QuoteAppError {
     #[error("database operation failed: {0}")]
     DatabaseError(#[from] sqlx::Error),
 }
+
+#[derive(Debug, Error)]
+pub enum QuoteWriteError {
+    #[error("quote not found")]
+    NotFound,
+
+    #[error("quote has changed since the given version")]
+    PreconditionFailed,
+
+    #[error("If-Match header required")]
+    PreconditionRequired,
+
+    #[error("database operation failed: {0}")]
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for QuoteWriteError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => QuoteWriteError::NotFound,
+            e => QuoteWriteError::Database(e),
+        }
+    }
+}
+
+impl IntoResponse for QuoteWriteError {
+    fn into_response(self) -> Response {
+        let (status, error_message) = match self {
+            QuoteWriteError::NotFound => (StatusCode::NOT_FOUND, "Quote not found."),
+            QuoteWriteError::PreconditionFailed => (
+                StatusCode::PRECONDITION_FAILED,
+                "Quote has been modified; fetch it again and retry with the new ETag.",
+            ),
+            QuoteWriteError::PreconditionRequired => (
+                StatusCode::PRECONDITION_REQUIRED,
+                "Send the quote's current ETag in an If-Match header.",
+            ),
+            QuoteWriteError::Database(e) => {
+                tracing::error!("API: quote write failed: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error.")
+            }
+        };
+        let body = Json(serde_json::json!({ "error": error_message }));
+        (status, body).into_response()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum QuoteAddError {
+    #[error("a quote with this id already exists")]
+    IdTaken,
+
+    #[error("quote resembles existing quotes")]
+    Duplicate(Vec<DuplicateMatch>),
+
+    #[error("database operation failed: {0}")]
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for QuoteAddError {
+    fn from(e: sqlx::Error) -> Self {
+        if e.as_database_error().is_some_and(|db_err| db_err.is_unique_violation()) {
+            QuoteAddError::IdTaken
+        } else {
+            QuoteAddError::Database(e)
+        }
+    }
+}
+
+impl IntoResponse for QuoteAddError {
+    fn into_response(self) -> Response {
+        let body = match self {
+            QuoteAddError::IdTaken => {
+                serde_json::json!({ "error": "A quote with this id already exists." })
+            }
+            QuoteAddError::Duplicate(matches) => serde_json::json!({
+                "error": "Quote resembles existing quotes; add force=true to add it anyway.",
+                "duplicates": matches,
+            }),
+            QuoteAddError::Database(e) => {
+                tracing::error!("API: quote add failed: {}", e);
+                let body = Json(serde_json::json!({ "error": "Internal server error." }));
+                return (StatusCode::INTERNAL_SERVER_ERROR, body).into_response();
+            }
+        };
+        (StatusCode::CONFLICT, Json(body)).into_response()
+    }
+}
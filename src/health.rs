@@ -0,0 +1,134 @@
+// health.rs
+use crate::authjwt;
+use crate::AppState;
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+#[derive(Serialize)]
+struct Check {
+    name: &'static str,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl Check {
+    fn new(name: &'static str, result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Self { name, ok: true, detail: None },
+            Err(detail) => Self { name, ok: false, detail: Some(detail) },
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report {
+    status: &'static str,
+    checks: Vec<Check>,
+}
+
+impl IntoResponse for Report {
+    fn into_response(self) -> Response {
+        let status = if self.checks.iter().all(|check| check.ok) {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+/// Liveness: the process is up and serving requests.
+pub async fn healthz() -> Response {
+    Json(Report {
+        status: "ok",
+        checks: Vec::new(),
+    })
+    .into_response()
+}
+
+async fn check_database(db: &SqlitePool) -> Result<(), String> {
+    sqlx::query_scalar!("SELECT 1 as \"one!: i64\";")
+        .fetch_one(db)
+        .await
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+async fn check_migrations(db: &SqlitePool) -> Result<(), String> {
+    let mut conn = db.acquire().await.map_err(|e| e.to_string())?;
+    let applied: HashSet<i64> = conn
+        .list_applied_migrations()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    let pending: Vec<String> = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .map(|migration| migration.version.to_string())
+        .collect();
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("pending migrations: {}", pending.join(", ")))
+    }
+}
+
+async fn check_quotes(db: &SqlitePool) -> Result<(), String> {
+    let any = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM quotes WHERE deleted_at IS NULL) as "any!: bool";"#
+    )
+    .fetch_one(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    if any {
+        Ok(())
+    } else {
+        Err("no quotes loaded".to_string())
+    }
+}
+
+/// Readiness: everything a request needs is in place, and the server is
+/// not draining for shutdown.
+pub async fn readyz(State(app_state): State<Arc<RwLock<AppState>>>) -> Response {
+    let state_guard = app_state.read().await;
+    let db = &state_guard.db;
+
+    let draining = state_guard.draining.load(Ordering::Acquire);
+    let jwt_keys = if authjwt::keys_usable(&state_guard.jwt_keys) {
+        Ok(())
+    } else {
+        Err("JWT keys cannot sign and verify tokens".to_string())
+    };
+    let checks = vec![
+        Check::new(
+            "shutdown",
+            if draining { Err("shutting down".to_string()) } else { Ok(()) },
+        ),
+        Check::new("database", check_database(db).await),
+        Check::new("migrations", check_migrations(db).await),
+        Check::new("jwt_keys", jwt_keys),
+        Check::new("quotes", check_quotes(db).await),
+    ];
+    let status = if checks.iter().all(|check| check.ok) {
+        "ok"
+    } else {
+        "unavailable"
+    };
+    Report { status, checks }.into_response()
+}
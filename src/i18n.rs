@@ -0,0 +1,170 @@
+// i18n.rs
+use crate::translation;
+use axum::http::HeaderMap;
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use fluent_syntax::ast::Entry;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use unic_langid::LanguageIdentifier;
+
+pub const LOCALE_COOKIE: &str = "quote_locale";
+
+/// UI catalogs, compiled in from `locales/<locale>/main.ftl`. The first is
+/// the default. Codes are lowercase, as `translation::normalize_lang` makes
+/// requested tags.
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en/main.ftl")),
+    ("es", include_str!("../locales/es/main.ftl")),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum I18nError {
+    #[error("{0} is not a valid locale")]
+    InvalidLocale(&'static str),
+    #[error("catalog for {locale} is invalid: {detail}")]
+    Catalog {
+        locale: &'static str,
+        detail: String,
+    },
+}
+
+/// A locale offered in the language picker, named in its own language.
+#[derive(Clone, Serialize)]
+pub struct LocaleChoice {
+    pub code: String,
+    pub name: String,
+}
+
+pub struct Locale {
+    pub code: &'static str,
+    bundle: FluentBundle<FluentResource>,
+    /// Every message formatted without arguments, for the page templates.
+    messages: BTreeMap<String, String>,
+}
+
+impl Locale {
+    fn load(code: &'static str, source: &str) -> Result<Self, I18nError> {
+        let langid: LanguageIdentifier =
+            code.parse().map_err(|_| I18nError::InvalidLocale(code))?;
+        let resource = FluentResource::try_new(source.to_string()).map_err(|(_, errors)| {
+            I18nError::Catalog {
+                locale: code,
+                detail: format!("{:?}", errors),
+            }
+        })?;
+        let ids: Vec<String> = resource
+            .entries()
+            .filter_map(|entry| match entry {
+                Entry::Message(message) => Some(message.id.name.to_string()),
+                _ => None,
+            })
+            .collect();
+
+        let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+        // Isolation marks around arguments end up verbatim in HTML attributes.
+        bundle.set_use_isolating(false);
+        bundle
+            .add_resource(resource)
+            .map_err(|errors| I18nError::Catalog {
+                locale: code,
+                detail: format!("{:?}", errors),
+            })?;
+
+        // Messages that take arguments are formatted again when used, so
+        // their missing arguments here are not errors.
+        let messages = ids
+            .into_iter()
+            .filter_map(|id| {
+                let pattern = bundle.get_message(&id)?.value()?;
+                let text = bundle.format_pattern(pattern, None, &mut Vec::new());
+                Some((id, text.into_owned()))
+            })
+            .collect();
+        Ok(Self {
+            code,
+            bundle,
+            messages,
+        })
+    }
+
+    /// Formats message `key`. A missing message is logged and shown as its key.
+    pub fn format(&self, key: &str, args: Option<&FluentArgs>) -> String {
+        let Some(pattern) = self
+            .bundle
+            .get_message(key)
+            .and_then(|message| message.value())
+        else {
+            tracing::error!("Locale {}: no message {}", self.code, key);
+            return key.to_string();
+        };
+        let mut errors = Vec::new();
+        let text = self.bundle.format_pattern(pattern, args, &mut errors);
+        if !errors.is_empty() {
+            tracing::error!(
+                "Locale {}: failed to format {}: {:?}",
+                self.code,
+                key,
+                errors
+            );
+        }
+        text.into_owned()
+    }
+
+    pub fn text(&self, key: &str) -> String {
+        self.format(key, None)
+    }
+
+    pub fn messages(&self) -> &BTreeMap<String, String> {
+        &self.messages
+    }
+}
+
+/// The locales the web UI is available in.
+pub struct Locales {
+    locales: Vec<Locale>,
+}
+
+impl Locales {
+    pub fn load() -> Result<Self, I18nError> {
+        let locales = CATALOGS
+            .iter()
+            .map(|&(code, source)| Locale::load(code, source))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { locales })
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Locale> {
+        self.locales.iter().find(|locale| locale.code == code)
+    }
+
+    pub fn default_locale(&self) -> &Locale {
+        &self.locales[0]
+    }
+
+    /// Best match for the request's `Accept-Language`, trying shorter forms
+    /// of each tag (`es-mx` matches `es`), else the default.
+    pub fn negotiate(&self, headers: &HeaderMap) -> &Locale {
+        translation::preferred_languages(headers, None)
+            .iter()
+            .find_map(|tag| {
+                let mut range = tag.as_str();
+                loop {
+                    if let Some(locale) = self.get(range) {
+                        return Some(locale);
+                    }
+                    range = range.rsplit_once('-')?.0;
+                }
+            })
+            .unwrap_or_else(|| self.default_locale())
+    }
+
+    pub fn choices(&self) -> Vec<LocaleChoice> {
+        self.locales
+            .iter()
+            .map(|locale| LocaleChoice {
+                code: locale.code.to_string(),
+                name: locale.text("locale-name"),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,268 @@
+// idempotency.rs
+use crate::authjwt;
+use crate::ratelimit::{ClientIp, RouteGroup};
+use crate::AppState;
+use axum::{
+    body::{self, Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const REPLAYED: &str = "idempotent-replayed";
+
+/// Request bodies larger than this are not buffered for fingerprinting.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Seconds a claim without a response holds its key. A request that never
+/// finished, say because the client hung up, stops blocking retries after it.
+const CLAIM_LEASE_SECS: i64 = 60;
+
+struct StoredResponse {
+    fingerprint: String,
+    status_code: Option<i64>,
+    content_type: Option<String>,
+    body: Option<Vec<u8>>,
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+fn fingerprint(method: &Method, uri: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b" ");
+    hasher.update(uri);
+    hasher.update(b"\n");
+    hasher.update(body);
+    format!("{:x}", hasher.finalize())
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= 255 && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = stored
+        .status_code
+        .and_then(|code| u16::try_from(code).ok())
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = Response::new(Body::from(stored.body.unwrap_or_default()));
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    if let Some(value) = stored
+        .content_type
+        .and_then(|value| HeaderValue::from_str(&value).ok())
+    {
+        headers.insert(header::CONTENT_TYPE, value);
+    }
+    headers.insert(REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+/// What became of an attempt to claim a key.
+enum Claim {
+    /// The key is this request's, claimed at the given time.
+    Claimed(DateTime<Utc>),
+    /// Another request claimed it first.
+    Taken(StoredResponse),
+}
+
+/// Claims `key` for this request, taking over a claim whose lease ran out
+/// before a response was stored.
+async fn claim_key(
+    db: &SqlitePool,
+    scope: &str,
+    key: &str,
+    fingerprint: &str,
+    window: TimeDelta,
+) -> Result<Claim, sqlx::Error> {
+    let now = Utc::now();
+    let cutoff = now - window;
+    let lease_cutoff = now - TimeDelta::seconds(CLAIM_LEASE_SECS);
+    sqlx::query!(
+        "DELETE FROM idempotency_keys
+        WHERE created_at < $1 OR (status_code IS NULL AND created_at < $2)",
+        cutoff,
+        lease_cutoff
+    )
+    .execute(db)
+    .await?;
+
+    let claimed = sqlx::query!(
+        "INSERT OR IGNORE INTO idempotency_keys (scope, idempotency_key, fingerprint, created_at)
+        VALUES ($1, $2, $3, $4)",
+        scope,
+        key,
+        fingerprint,
+        now,
+    )
+    .execute(db)
+    .await?;
+    if claimed.rows_affected() == 1 {
+        return Ok(Claim::Claimed(now));
+    }
+
+    sqlx::query_as!(
+        StoredResponse,
+        "SELECT fingerprint, status_code, content_type, body FROM idempotency_keys
+        WHERE scope = $1 AND idempotency_key = $2",
+        scope,
+        key
+    )
+    .fetch_one(db)
+    .await
+    .map(Claim::Taken)
+}
+
+/// Stores the response on this request's claim, unless the claim's lease ran
+/// out and another request took the key over.
+async fn store_response(
+    db: &SqlitePool,
+    scope: &str,
+    key: &str,
+    claimed_at: DateTime<Utc>,
+    status: StatusCode,
+    content_type: Option<&str>,
+    body: &[u8],
+) -> Result<(), sqlx::Error> {
+    let status_code = i64::from(status.as_u16());
+    sqlx::query!(
+        "UPDATE idempotency_keys SET status_code = $4, content_type = $5, body = $6
+        WHERE scope = $1 AND idempotency_key = $2 AND created_at = $3",
+        scope,
+        key,
+        claimed_at,
+        status_code,
+        content_type,
+        body,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+async fn release_key(
+    db: &SqlitePool,
+    scope: &str,
+    key: &str,
+    claimed_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "DELETE FROM idempotency_keys
+        WHERE scope = $1 AND idempotency_key = $2 AND created_at = $3 AND status_code IS NULL",
+        scope,
+        key,
+        claimed_at
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Middleware making POST requests that carry an `Idempotency-Key` safe to
+/// retry. The first response for a key is stored and replayed for retries
+/// with the same body; reusing the key for a different request is a 422.
+/// Server errors are not stored so the retry gets another go, and a request
+/// that never finished holds its key only for a short lease. Auth endpoints
+/// are left alone so issued tokens are never stored.
+pub async fn idempotency(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let auth = RouteGroup::classify(request.method(), request.uri().path()) == RouteGroup::Auth;
+    if request.method() != Method::POST || auth {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if is_valid_key(key) => key.to_string(),
+        _ => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "Idempotency-Key must be 1 to 255 visible ASCII characters.",
+            )
+        }
+    };
+
+    let (db, window, subject) = {
+        let app_reader = app_state.read().await;
+        let subject = authjwt::bearer_subject(request.headers(), &app_reader.jwt_keys);
+        (app_reader.db.clone(), app_reader.idempotency_window, subject)
+    };
+    let scope = match (subject, request.extensions().get::<ClientIp>()) {
+        (Some(sub), _) => format!("sub:{}", sub),
+        (None, Some(ClientIp(ip))) => format!("ip:{}", ip),
+        (None, None) => "anonymous".to_string(),
+    };
+
+    let (parts, body) = request.into_parts();
+    let body = match body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => {
+            return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body too large.")
+        }
+    };
+    let request_fingerprint = fingerprint(&parts.method, &parts.uri.to_string(), &body);
+
+    let claimed_at = match claim_key(&db, &scope, &key, &request_fingerprint, window).await {
+        Ok(Claim::Claimed(claimed_at)) => claimed_at,
+        Ok(Claim::Taken(stored)) if stored.fingerprint != request_fingerprint => {
+            return error_response(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency-Key was already used for a different request.",
+            )
+        }
+        Ok(Claim::Taken(stored)) if stored.status_code.is_none() => {
+            return error_response(
+                StatusCode::CONFLICT,
+                "A request with this Idempotency-Key is still in progress.",
+            )
+        }
+        Ok(Claim::Taken(stored)) => {
+            tracing::debug!("Replaying response for idempotency key {}", key);
+            return replay(stored);
+        }
+        Err(e) => {
+            tracing::error!("Failed to look up idempotency key: {}", e);
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error.");
+        }
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let (parts, body) = response.into_parts();
+    let body: Bytes = match body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Failed to buffer response for idempotency key {}: {}", key, e);
+            let _ = release_key(&db, &scope, &key, claimed_at).await;
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error.");
+        }
+    };
+
+    let outcome = if parts.status.is_server_error() {
+        release_key(&db, &scope, &key, claimed_at).await
+    } else {
+        let content_type = parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        store_response(&db, &scope, &key, claimed_at, parts.status, content_type, &body).await
+    };
+    if let Err(e) = outcome {
+        tracing::error!("Failed to record idempotency key {}: {}", key, e);
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
@@ -0,0 +1,370 @@
+// index.rs
+use crate::quote::{self, normalize_speaker, normalize_tag, Quote, QuoteFilter};
+use crate::rng::QuoteRng;
+use crate::vote;
+use lru::LruCache;
+use roaring::RoaringBitmap;
+use sqlx::{types::Json, SqlitePool};
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+
+/// The parts of a quote the index can filter on.
+struct IndexedQuote {
+    /// SQLite rowid, matched against [`QuoteFilter::exclude_rows`].
+    row: i64,
+    speaker: String,
+    len: usize,
+    tags: Vec<String>,
+    /// Chance of a weighted pick, from the quote's votes.
+    weight: f64,
+}
+
+/// Live quote ids and the tags pointing at them. Every quote gets a slot
+/// number, and tags map to bitmaps of slots, so picking a random quote or
+/// intersecting tags never touches SQLite. Slots follow quote id order, as
+/// the SQL fallback does, so the nth set bit of a bitmap is the nth
+/// matching quote by id and a seed picks the same quote either way. Removed
+/// quotes leave empty slots until a rebuild compacts them.
+#[derive(Default)]
+struct IdIndex {
+    slots: Vec<Option<String>>,
+    slot_quotes: Vec<Option<IndexedQuote>>,
+    slot_of: HashMap<String, u32>,
+    live: RoaringBitmap,
+    by_tag: HashMap<String, RoaringBitmap>,
+    by_speaker: HashMap<String, RoaringBitmap>,
+}
+
+fn unindex(bitmaps: &mut HashMap<String, RoaringBitmap>, key: &str, slot: u32) {
+    if let Some(bitmap) = bitmaps.get_mut(key) {
+        bitmap.remove(slot);
+        if bitmap.is_empty() {
+            bitmaps.remove(key);
+        }
+    }
+}
+
+impl IdIndex {
+    /// Adds or replaces a quote. A quote already indexed keeps its slot;
+    /// a new one is appended when its id sorts last, and otherwise the
+    /// slots are renumbered to make room for it.
+    fn insert(&mut self, id: String, quote: IndexedQuote) {
+        if let Some(&slot) = self.slot_of.get(&id) {
+            self.clear_slot(slot);
+            self.fill(slot, id, quote);
+            return;
+        }
+        let sorts_last = self
+            .live
+            .max()
+            .and_then(|slot| self.slots[slot as usize].as_ref())
+            .is_none_or(|last| *last < id);
+        if sorts_last {
+            self.push(id, quote);
+        } else {
+            self.rebuild(Some((id, quote)));
+        }
+    }
+
+    fn remove(&mut self, id: &str) {
+        let Some(slot) = self.slot_of.remove(id) else {
+            return;
+        };
+        self.clear_slot(slot);
+        self.slots[slot as usize] = None;
+        self.live.remove(slot);
+        if self.slots.len() as u64 > 2 * self.live.len() + 64 {
+            self.rebuild(None);
+        }
+    }
+
+    fn push(&mut self, id: String, quote: IndexedQuote) {
+        self.slots.push(None);
+        self.slot_quotes.push(None);
+        self.fill((self.slots.len() - 1) as u32, id, quote);
+    }
+
+    fn fill(&mut self, slot: u32, id: String, quote: IndexedQuote) {
+        for tag in &quote.tags {
+            self.by_tag.entry(tag.clone()).or_default().insert(slot);
+        }
+        self.by_speaker
+            .entry(quote.speaker.clone())
+            .or_default()
+            .insert(slot);
+        self.slot_of.insert(id.clone(), slot);
+        self.slots[slot as usize] = Some(id);
+        self.slot_quotes[slot as usize] = Some(quote);
+        self.live.insert(slot);
+    }
+
+    /// Drops a slot's quote from the tag and speaker bitmaps.
+    fn clear_slot(&mut self, slot: u32) {
+        if let Some(quote) = self.slot_quotes[slot as usize].take() {
+            for tag in &quote.tags {
+                unindex(&mut self.by_tag, tag, slot);
+            }
+            unindex(&mut self.by_speaker, &quote.speaker, slot);
+        }
+    }
+
+    /// Renumbers the live quotes, plus `extra`, into consecutive slots in
+    /// id order.
+    fn rebuild(&mut self, extra: Option<(String, IndexedQuote)>) {
+        let old = std::mem::take(self);
+        let mut quotes: Vec<(String, IndexedQuote)> = old
+            .slots
+            .into_iter()
+            .zip(old.slot_quotes)
+            .filter_map(|(id, quote)| Some((id?, quote?)))
+            .chain(extra)
+            .collect();
+        quotes.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        for (id, quote) in quotes {
+            self.push(id, quote);
+        }
+    }
+
+    fn pick(&self, candidates: &RoaringBitmap, rng: &mut QuoteRng) -> Option<String> {
+        let nth = rng.index(candidates.len())? as u32;
+        let slot = candidates.select(nth)?;
+        self.slots[slot as usize].clone()
+    }
+
+    /// Like [`IdIndex::pick`], but by each quote's popularity weight.
+    fn pick_weighted(&self, candidates: &RoaringBitmap, rng: &mut QuoteRng) -> Option<String> {
+        let weighted = candidates
+            .iter()
+            .filter_map(|slot| {
+                let weight = self.slot_quotes[slot as usize].as_ref()?.weight;
+                Some((slot, weight))
+            })
+            .collect();
+        let slot = rng.choose_weighted(weighted)?;
+        self.slots[slot as usize].clone()
+    }
+
+    /// Intersection of the bitmaps, smallest first; `None` if there are none.
+    fn intersect(mut bitmaps: Vec<&RoaringBitmap>) -> Option<RoaringBitmap> {
+        bitmaps.sort_by_key(|bitmap| bitmap.len());
+        let (smallest, rest) = bitmaps.split_first()?;
+        Some(
+            rest.iter()
+                .fold((*smallest).clone(), |acc, bitmap| acc & *bitmap),
+        )
+    }
+
+    /// A random quote carrying every one of `tags`.
+    fn pick_tagged(&self, tags: &[String], rng: &mut QuoteRng) -> Option<String> {
+        let bitmaps = tags
+            .iter()
+            .map(|tag| self.by_tag.get(tag))
+            .collect::<Option<Vec<_>>>()?;
+        self.pick(&Self::intersect(bitmaps)?, rng)
+    }
+
+    fn pick_matching(&self, filter: &QuoteFilter, rng: &mut QuoteRng) -> Option<String> {
+        let bitmaps = filter
+            .tags
+            .iter()
+            .map(|tag| self.by_tag.get(tag))
+            .chain(filter.speaker.iter().map(|speaker| self.by_speaker.get(speaker)))
+            .collect::<Option<Vec<_>>>()?;
+        let mut matches = Self::intersect(bitmaps).unwrap_or_else(|| self.live.clone());
+
+        for id in &filter.exclude {
+            if let Some(slot) = self.slot_of.get(id) {
+                matches.remove(*slot);
+            }
+        }
+        if !filter.exclude_rows.is_empty() {
+            let excluded: RoaringBitmap = matches
+                .iter()
+                .filter(|slot| {
+                    self.slot_quotes[*slot as usize].as_ref().is_some_and(|quote| {
+                        u32::try_from(quote.row).is_ok_and(|row| filter.exclude_rows.contains(row))
+                    })
+                })
+                .collect();
+            matches -= excluded;
+        }
+        if let Some(max_len) = filter.max_len {
+            let too_long: RoaringBitmap = matches
+                .iter()
+                .filter(|slot| {
+                    self.slot_quotes[*slot as usize]
+                        .as_ref()
+                        .is_none_or(|quote| quote.len > max_len)
+                })
+                .collect();
+            matches -= too_long;
+        }
+        if filter.weighted {
+            return self.pick_weighted(&matches, rng);
+        }
+        self.pick(&matches, rng)
+    }
+}
+
+type QuoteRows = LruCache<String, (Quote, Vec<String>)>;
+
+/// In-memory quote selection plus a bounded cache of full rows, kept in
+/// step with SQLite by calling [`QuoteIndex::reload`] after every write.
+/// Either half can be switched off, in which case queries go to SQLite.
+pub struct QuoteIndex {
+    ids: Option<RwLock<IdIndex>>,
+    rows: Option<Mutex<QuoteRows>>,
+    /// Bumped by every reload so a read racing a write does not cache the
+    /// row it fetched before the write.
+    generation: AtomicU64,
+}
+
+impl QuoteIndex {
+    pub async fn load(
+        db: &SqlitePool,
+        use_index: bool,
+        cache_size: usize,
+    ) -> Result<Self, sqlx::Error> {
+        let ids = if use_index {
+            let rows = sqlx::query!(
+                r#"SELECT rowid as "row!: i64", id, whos_there, LENGTH(answer_who) as "len!: i64", upvotes, downvotes,
+                    (SELECT json_group_array(tag_norm) FROM quote_tags WHERE quote_id = quotes.id) as "tags!: Json<Vec<String>>"
+                FROM quotes WHERE deleted_at IS NULL ORDER BY id;"#
+            )
+            .fetch_all(db)
+            .await?;
+
+            let mut index = IdIndex::default();
+            for row in rows {
+                let quote = IndexedQuote {
+                    row: row.row,
+                    speaker: normalize_speaker(&row.whos_there),
+                    len: row.len as usize,
+                    tags: row.tags.0,
+                    weight: vote::popularity_weight(row.upvotes, row.downvotes),
+                };
+                index.insert(row.id, quote);
+            }
+            tracing::info!("Indexed {} quotes and {} tags", index.live.len(), index.by_tag.len());
+            Some(RwLock::new(index))
+        } else {
+            None
+        };
+        let rows = NonZeroUsize::new(cache_size).map(|size| Mutex::new(LruCache::new(size)));
+
+        Ok(Self {
+            ids,
+            rows,
+            generation: AtomicU64::new(0),
+        })
+    }
+
+    pub async fn tagged_id<'a, I>(
+        &self,
+        db: &SqlitePool,
+        search_tags: I,
+        rng: &mut QuoteRng,
+    ) -> Result<Option<String>, sqlx::Error>
+    where
+        I: Iterator<Item = &'a str> + Send,
+    {
+        let Some(ids) = &self.ids else {
+            return quote::get_tagged_quote_id_from_db(db, search_tags, rng).await;
+        };
+        let tags: Vec<String> = search_tags
+            .map(normalize_tag)
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        if tags.is_empty() {
+            return Ok(None);
+        }
+        Ok(ids.read().unwrap().pick_tagged(&tags, rng))
+    }
+
+    /// A random quote passing `filter`, or `None` if nothing does.
+    pub async fn matching_id(
+        &self,
+        db: &SqlitePool,
+        filter: &QuoteFilter,
+        rng: &mut QuoteRng,
+    ) -> Result<Option<String>, sqlx::Error> {
+        match &self.ids {
+            Some(ids) => Ok(ids.read().unwrap().pick_matching(filter, rng)),
+            None => quote::get_filtered_quote_id_from_db(db, filter, rng).await,
+        }
+    }
+
+    /// The SQLite rowid of a live quote, `None` if it is gone.
+    pub async fn row_of(&self, db: &SqlitePool, quote_id: &str) -> Result<Option<i64>, sqlx::Error> {
+        let Some(ids) = &self.ids else {
+            return quote::get_quote_row_from_db(db, quote_id).await;
+        };
+        let ids = ids.read().unwrap();
+        Ok(ids
+            .slot_of
+            .get(quote_id)
+            .and_then(|slot| ids.slot_quotes[*slot as usize].as_ref())
+            .map(|quote| quote.row))
+    }
+
+    pub async fn get_quote(
+        &self,
+        db: &SqlitePool,
+        quote_id: &str,
+    ) -> Result<(Quote, Vec<String>), sqlx::Error> {
+        if let Some(rows) = &self.rows {
+            if let Some(hit) = rows.lock().unwrap().get(quote_id) {
+                return Ok(hit.clone());
+            }
+        }
+        let generation = self.generation.load(Ordering::Acquire);
+        let found = quote::get_quote_by_id_from_db(db, quote_id).await?;
+        if let Some(rows) = &self.rows {
+            let mut rows = rows.lock().unwrap();
+            if self.generation.load(Ordering::Acquire) == generation {
+                rows.put(quote_id.to_string(), found.clone());
+            }
+        }
+        Ok(found)
+    }
+
+    /// Re-reads `quote_id` after a write, dropping it if it is gone or in
+    /// the trash.
+    pub async fn reload(&self, db: &SqlitePool, quote_id: &str) -> Result<(), sqlx::Error> {
+        if let Some(rows) = &self.rows {
+            let mut rows = rows.lock().unwrap();
+            self.generation.fetch_add(1, Ordering::AcqRel);
+            rows.pop(quote_id);
+        }
+        let Some(ids) = &self.ids else {
+            return Ok(());
+        };
+
+        let row = sqlx::query!(
+            r#"SELECT rowid as "row!: i64", whos_there, LENGTH(answer_who) as "len!: i64", upvotes, downvotes,
+                (SELECT json_group_array(tag_norm) FROM quote_tags WHERE quote_id = quotes.id) as "tags!: Json<Vec<String>>"
+            FROM quotes WHERE id = $1 AND deleted_at IS NULL;"#,
+            quote_id
+        )
+        .fetch_optional(db)
+        .await?;
+
+        let mut ids = ids.write().unwrap();
+        match row {
+            Some(row) => {
+                let quote = IndexedQuote {
+                    row: row.row,
+                    speaker: normalize_speaker(&row.whos_there),
+                    len: row.len as usize,
+                    tags: row.tags.0,
+                    weight: vote::popularity_weight(row.upvotes, row.downvotes),
+                };
+                ids.insert(quote_id.to_string(), quote);
+            }
+            None => ids.remove(quote_id),
+        }
+        Ok(())
+    }
+}
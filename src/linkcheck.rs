@@ -0,0 +1,344 @@
+// linkcheck.rs
+use crate::metrics::QueryTimer;
+use chrono::{DateTime, TimeDelta, Utc};
+use reqwest::{redirect, Client, Method, StatusCode, Url};
+use serde::Serialize;
+use sqlx::{types::Json, SqlitePool};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use utoipa::ToSchema;
+
+/// Redirects followed before a link counts as broken.
+const MAX_REDIRECTS: usize = 10;
+/// Longest wait between passes, so newly cited links are checked soon.
+const MAX_IDLE: Duration = Duration::from_secs(60);
+const USER_AGENT: &str = concat!(
+    "quote_server/",
+    env!("CARGO_PKG_VERSION"),
+    " (link checker)"
+);
+
+#[derive(Debug, Clone)]
+pub struct LinkCheckConfig {
+    /// How long a result stands before the link is checked again.
+    pub interval: Duration,
+    /// Requests in flight at once, across all hosts.
+    pub concurrency: usize,
+    /// Pause between requests to the same host.
+    pub host_delay: Duration,
+    pub timeout: Duration,
+}
+
+/// The outcome of fetching one link.
+#[derive(Debug, Clone)]
+pub struct LinkCheck {
+    pub url: String,
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub redirects: Vec<String>,
+    pub broken: bool,
+}
+
+/// A source link that failed its last check, with the quotes citing it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BrokenLink {
+    pub url: String,
+    /// Final HTTP status, absent when no response came back.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    /// URLs redirected to, in order.
+    pub redirects: Vec<String>,
+    pub checked_at: DateTime<Utc>,
+    /// When the link was first found broken in its current run of failures.
+    pub broken_since: Option<DateTime<Utc>>,
+    pub quote_ids: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct LinkCheckSummary {
+    pub checked: usize,
+    pub broken: usize,
+}
+
+pub fn client(timeout: Duration) -> reqwest::Result<Client> {
+    Client::builder()
+        .user_agent(USER_AGENT)
+        .redirect(redirect::Policy::none())
+        .timeout(timeout)
+        .build()
+}
+
+/// The error and its causes, without the URL reqwest repeats in it.
+fn describe(e: reqwest::Error) -> String {
+    let e = e.without_url();
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// Fetches `url` with HEAD, falling back to GET for servers that refuse
+/// HEAD, and follows redirects by hand so each hop is recorded. Waits
+/// `delay` before every hop after the first.
+pub async fn check_url(client: &Client, url: &str, delay: Duration) -> LinkCheck {
+    let mut check = LinkCheck {
+        url: url.to_string(),
+        status: None,
+        error: None,
+        redirects: Vec::new(),
+        broken: true,
+    };
+    let mut current = match Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => parsed,
+        _ => {
+            check.error = Some("not an http(s) URL".to_string());
+            return check;
+        }
+    };
+
+    let mut method = Method::HEAD;
+    let mut first = true;
+    loop {
+        if !std::mem::take(&mut first) {
+            tokio::time::sleep(delay).await;
+        }
+        let response = match client.request(method.clone(), current.clone()).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                check.error = Some(describe(e));
+                return check;
+            }
+        };
+        let status = response.status();
+        check.status = Some(status.as_u16());
+
+        if method == Method::HEAD
+            && matches!(
+                status,
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            )
+        {
+            method = Method::GET;
+            continue;
+        }
+        if !status.is_redirection() {
+            check.broken = status.is_client_error() || status.is_server_error();
+            return check;
+        }
+
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|location| current.join(location).ok());
+        let Some(next) = location else {
+            check.error = Some("redirect without a usable Location".to_string());
+            return check;
+        };
+        if check.redirects.len() == MAX_REDIRECTS {
+            check.error = Some(format!("more than {} redirects", MAX_REDIRECTS));
+            return check;
+        }
+        check.redirects.push(next.to_string());
+        current = next;
+    }
+}
+
+/// Source links of live quotes, from their `source` and their cited
+/// sources, that have not been checked since `checked_before`; all of
+/// them without it.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_due_links"))]
+async fn get_due_links_from_db(
+    db: &SqlitePool,
+    checked_before: Option<DateTime<Utc>>,
+) -> Result<Vec<String>, sqlx::Error> {
+    let _timer = QueryTimer::start("get_due_links");
+    sqlx::query_scalar!(
+        r#"SELECT refs.url as "url!: String" FROM (
+            SELECT TRIM(source) AS url FROM quotes WHERE deleted_at IS NULL AND TRIM(source) <> ''
+            UNION
+            SELECT s.url FROM quote_sources s JOIN quotes q ON q.id = s.quote_id AND q.deleted_at IS NULL
+        ) refs
+        WHERE NOT EXISTS (SELECT 1 FROM link_checks l WHERE l.url = refs.url AND $1 IS NOT NULL AND l.checked_at >= $1)
+        ORDER BY refs.url;"#,
+        checked_before
+    )
+    .fetch_all(db)
+    .await
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "record_link_check"))]
+async fn record_link_check(db: &SqlitePool, check: &LinkCheck) -> Result<(), sqlx::Error> {
+    let _timer = QueryTimer::start("record_link_check");
+    let now = Utc::now();
+    let status = check.status.map(i64::from);
+    let redirects = Json(&check.redirects);
+    sqlx::query!(
+        "INSERT INTO link_checks (url, status, error, redirects, broken, checked_at, broken_since)
+        VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $5 THEN $6 END)
+        ON CONFLICT (url) DO UPDATE SET
+            status = excluded.status, error = excluded.error, redirects = excluded.redirects,
+            broken = excluded.broken, checked_at = excluded.checked_at,
+            broken_since = CASE WHEN excluded.broken
+                THEN COALESCE(link_checks.broken_since, excluded.checked_at) END",
+        check.url,
+        status,
+        check.error,
+        redirects,
+        check.broken,
+        now,
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// Forgets results for links no live quote cites any more.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "prune_link_checks"))]
+async fn prune_link_checks(db: &SqlitePool) -> Result<u64, sqlx::Error> {
+    let _timer = QueryTimer::start("prune_link_checks");
+    let pruned = sqlx::query!(
+        "DELETE FROM link_checks WHERE url NOT IN (
+            SELECT TRIM(source) FROM quotes WHERE deleted_at IS NULL
+            UNION
+            SELECT s.url FROM quote_sources s JOIN quotes q ON q.id = s.quote_id AND q.deleted_at IS NULL
+        )"
+    )
+    .execute(db)
+    .await?;
+    Ok(pruned.rows_affected())
+}
+
+struct BrokenLinkRow {
+    url: String,
+    status: Option<i64>,
+    error: Option<String>,
+    redirects: Json<Vec<String>>,
+    checked_at: DateTime<Utc>,
+    broken_since: Option<DateTime<Utc>>,
+    quote_ids: Json<Vec<String>>,
+}
+
+/// Links that failed their last check, longest broken first.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_broken_links"))]
+pub async fn get_broken_links_from_db(db: &SqlitePool) -> Result<Vec<BrokenLink>, sqlx::Error> {
+    let _timer = QueryTimer::start("get_broken_links");
+    let rows = sqlx::query_as!(
+        BrokenLinkRow,
+        r#"SELECT l.url, l.status, l.error, l.redirects as "redirects: Json<Vec<String>>",
+            l.checked_at as "checked_at: DateTime<Utc>", l.broken_since as "broken_since: DateTime<Utc>",
+            (SELECT json_group_array(id) FROM (
+                SELECT id FROM quotes WHERE TRIM(source) = l.url AND deleted_at IS NULL
+                UNION
+                SELECT s.quote_id FROM quote_sources s JOIN quotes q ON q.id = s.quote_id AND q.deleted_at IS NULL
+                WHERE s.url = l.url
+            )) as "quote_ids!: Json<Vec<String>>"
+        FROM link_checks l WHERE l.broken
+        ORDER BY l.broken_since, l.url;"#
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| BrokenLink {
+            url: row.url,
+            status: row.status.and_then(|status| u16::try_from(status).ok()),
+            error: row.error,
+            redirects: row.redirects.0,
+            checked_at: row.checked_at,
+            broken_since: row.broken_since,
+            quote_ids: row.quote_ids.0,
+        })
+        .collect())
+}
+
+/// Checks every link due for a check, or all of them with `force`. Each
+/// host's links are fetched one at a time, `host_delay` apart, while up to
+/// `concurrency` hosts are fetched from at once.
+pub async fn check_links(
+    db: &SqlitePool,
+    client: &Client,
+    config: &LinkCheckConfig,
+    force: bool,
+) -> Result<LinkCheckSummary, sqlx::Error> {
+    let checked_before = match force {
+        true => None,
+        false => TimeDelta::from_std(config.interval)
+            .ok()
+            .and_then(|age| Utc::now().checked_sub_signed(age)),
+    };
+    let mut by_host: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for url in get_due_links_from_db(db, checked_before).await? {
+        let host = Url::parse(&url)
+            .ok()
+            .and_then(|parsed| parsed.host_str().map(str::to_string))
+            .unwrap_or_default();
+        by_host.entry(host).or_default().push(url);
+    }
+
+    let permits = Arc::new(Semaphore::new(config.concurrency.max(1)));
+    let mut hosts = JoinSet::new();
+    for urls in by_host.into_values() {
+        let (db, client, permits) = (db.clone(), client.clone(), permits.clone());
+        let delay = config.host_delay;
+        hosts.spawn(async move {
+            let mut summary = LinkCheckSummary::default();
+            for (i, url) in urls.iter().enumerate() {
+                if i > 0 {
+                    tokio::time::sleep(delay).await;
+                }
+                let check = {
+                    let _permit = permits.acquire().await.expect("semaphore is never closed");
+                    check_url(&client, url, delay).await
+                };
+                let result = if check.broken { "broken" } else { "ok" };
+                metrics::counter!("link_checks_total", "result" => result).increment(1);
+                summary.checked += 1;
+                summary.broken += usize::from(check.broken);
+                record_link_check(&db, &check).await?;
+            }
+            Ok::<_, sqlx::Error>(summary)
+        });
+    }
+
+    let mut summary = LinkCheckSummary::default();
+    while let Some(joined) = hosts.join_next().await {
+        match joined {
+            Ok(host_summary) => {
+                let host_summary = host_summary?;
+                summary.checked += host_summary.checked;
+                summary.broken += host_summary.broken;
+            }
+            Err(e) => tracing::error!("Link check: host task failed: {}", e),
+        }
+    }
+    prune_link_checks(db).await?;
+    Ok(summary)
+}
+
+/// Keeps checking links in the background, each once per `interval`.
+pub fn spawn(db: SqlitePool, client: Client, config: LinkCheckConfig) {
+    tokio::spawn(async move {
+        let idle = config.interval.min(MAX_IDLE);
+        loop {
+            match check_links(&db, &client, &config, false).await {
+                Ok(summary) if summary.checked > 0 => tracing::info!(
+                    "Link check: checked {} links, {} broken",
+                    summary.checked,
+                    summary.broken
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::error!("Link check failed: {}", e),
+            }
+            tokio::time::sleep(idle).await;
+        }
+    });
+}
@@ -0,0 +1,78 @@
+// logging.rs
+use crate::telemetry;
+use axum::{
+    extract::{RawPathParams, Request},
+    middleware::Next,
+    response::Response,
+    RequestPartsExt,
+};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::{field::Empty, Span};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Installs the global subscriber, also exporting spans through
+/// `tracer_provider` when one is given.
+pub fn init(format: LogFormat, tracer_provider: Option<&SdkTracerProvider>) {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "quote_server=debug,tower_http=info".into());
+    let (text, json) = match format {
+        LogFormat::Text => (Some(fmt::layer()), None),
+        LogFormat::Json => (None, Some(fmt::layer().json())),
+    };
+    let otel = tracer_provider.map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("quote_server"))
+    });
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .with(otel)
+        .init();
+}
+
+/// Span for one request. `subject` and `quote_id` are filled in once known.
+pub fn make_span(request: &Request) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id,
+        subject = Empty,
+        quote_id = Empty,
+    );
+    telemetry::set_remote_parent(&span, request.headers());
+    span
+}
+
+pub fn record_subject(subject: &str) {
+    Span::current().record("subject", subject);
+}
+
+pub fn record_quote_id(quote_id: &str) {
+    Span::current().record("quote_id", quote_id);
+}
+
+/// Records the `{quote_id}` path parameter of the matched route, if any.
+pub async fn record_path_fields(request: Request, next: Next) -> Response {
+    let (mut parts, body) = request.into_parts();
+    if let Ok(params) = parts.extract::<RawPathParams>().await {
+        if let Some((_, quote_id)) = params.iter().find(|(key, _)| *key == "quote_id") {
+            record_quote_id(quote_id);
+        }
+    }
+    next.run(Request::from_parts(parts, body)).await
+}
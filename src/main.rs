@@ -1,33 +1,67 @@
 // main.rs
 // name: alex osorio trujillo
 mod api;
+mod assets;
 mod authjwt;
+mod cache;
+mod citation;
+mod duplicate;
 mod error;
+mod health;
+mod i18n;
+mod idempotency;
+mod index;
+mod linkcheck;
+mod logging;
+mod metrics;
 mod quote;
+mod ratelimit;
+mod revision;
+mod rng;
+mod session;
+mod submission;
+mod telemetry;
 mod templates;
+mod themes;
+mod translation;
+mod vote;
 mod web;
 
-use crate::quote::read_quotes_from_file;
+use crate::assets::Assets;
+use crate::duplicate::{DuplicateAction, DuplicatePolicy, TrigramIndex};
+use crate::i18n::Locales;
+use crate::index::QuoteIndex;
+use crate::linkcheck::LinkCheckConfig;
+use crate::logging::LogFormat;
+use crate::quote::{read_quotes_from_file, IMPORT_AUTHOR};
+use crate::ratelimit::{RateLimit, RateLimiter, RateLimits, TrustedProxy};
+use crate::session::RandomSessions;
+use crate::telemetry::{OtlpConfig, ResourceAttribute};
+use crate::themes::Themes;
 use axum::{
     http::{Method, StatusCode},
+    middleware,
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
     Router,
 };
 
 use clap::Parser;
+use metrics_exporter_prometheus::PrometheusHandle;
+use opentelemetry_sdk::trace::SdkTracerProvider;
 use sqlx::{migrate::MigrateDatabase, sqlite::SqliteConnectOptions, ConnectOptions, SqlitePool};
 use std::borrow::Cow;
+use std::net::SocketAddr;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::{net::TcpListener, signal, sync::RwLock, time::Duration};
 use tower_http::{
     cors::{Any, CorsLayer},
-    services::ServeFile,
-    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
 };
 
-use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 use utoipa::OpenApi;
 use utoipa_rapidoc::RapiDoc;
 use utoipa_swagger_ui::SwaggerUi;
@@ -42,15 +76,131 @@ struct Args {
     ip: String,
     #[arg(short, long, default_value = "3000", env = "PORT")]
     port: u16,
+    /// Permanently delete trashed quotes older than --trash-max-age-days, then exit.
+    #[arg(long)]
+    purge_trash: bool,
+    #[arg(long, default_value = "30", env = "TRASH_MAX_AGE_DAYS")]
+    trash_max_age_days: i64,
+    /// Print clusters of quotes that resemble each other, then exit.
+    #[arg(long)]
+    duplicate_report: bool,
+    /// What adding a quote that resembles an existing one does.
+    #[arg(long, value_enum, default_value = "reject", env = "DUPLICATES")]
+    duplicates: DuplicateAction,
+    /// Trigram similarity, from 0 to 1, from which quotes count as duplicates.
+    #[arg(long, default_value = "0.8", env = "DUPLICATE_THRESHOLD", value_parser = duplicate::parse_threshold)]
+    duplicate_threshold: f64,
+    /// Check every source link once, print the broken ones, then exit.
+    #[arg(long)]
+    check_links: bool,
+    /// Seconds before a source link is checked again; 0 turns the background checker off.
+    #[arg(long, default_value = "86400", env = "LINK_CHECK_INTERVAL")]
+    link_check_interval: u64,
+    /// Source link requests in flight at once.
+    #[arg(long, default_value = "8", env = "LINK_CHECK_CONCURRENCY")]
+    link_check_concurrency: usize,
+    /// Milliseconds between requests to the same host.
+    #[arg(long, default_value = "1000", env = "LINK_CHECK_HOST_DELAY_MS")]
+    link_check_host_delay_ms: u64,
+    /// Seconds to wait for a source link to answer.
+    #[arg(long, default_value = "10", env = "LINK_CHECK_TIMEOUT")]
+    link_check_timeout: u64,
+    /// Public quote submissions accepted per client address per hour.
+    #[arg(long, default_value = "5", env = "SUBMISSIONS_PER_HOUR")]
+    submissions_per_hour: i64,
+    /// Rate limits per route group, as N/SECONDS or "off".
+    #[arg(long, default_value = "120/60", env = "RATE_LIMIT_READ")]
+    rate_limit_read: RateLimit,
+    #[arg(long, default_value = "30/60", env = "RATE_LIMIT_WRITE")]
+    rate_limit_write: RateLimit,
+    #[arg(long, default_value = "5/60", env = "RATE_LIMIT_AUTH")]
+    rate_limit_auth: RateLimit,
+    #[arg(long, default_value = "10/60", env = "RATE_LIMIT_SUBMIT")]
+    rate_limit_submit: RateLimit,
+    /// Browser cache lifetime in seconds for quotes fetched by id.
+    #[arg(long, default_value = "60", env = "CACHE_MAX_AGE")]
+    cache_max_age: u32,
+    /// Shared (CDN) cache lifetime in seconds for quotes fetched by id.
+    #[arg(long, default_value = "3600", env = "CDN_MAX_AGE")]
+    cdn_max_age: u32,
+    /// Proxy addresses or CIDR blocks whose X-Forwarded-For header is trusted.
+    #[arg(long, env = "TRUSTED_PROXIES", value_delimiter = ',')]
+    trusted_proxies: Vec<TrustedProxy>,
+    /// Reject quote writes that carry no If-Match header with 428.
+    #[arg(long, env = "REQUIRE_IF_MATCH")]
+    require_if_match: bool,
+    /// Seconds a stored Idempotency-Key response is replayed for.
+    #[arg(long, default_value = "86400", env = "IDEMPOTENCY_WINDOW")]
+    idempotency_window: i64,
+    /// Pick random and tagged quotes with SQL queries instead of the in-memory index.
+    #[arg(long, env = "NO_QUOTE_INDEX")]
+    no_quote_index: bool,
+    /// Quotes kept in the in-memory row cache; 0 disables it.
+    #[arg(long, default_value = "1024", env = "QUOTE_CACHE_SIZE")]
+    quote_cache_size: usize,
+    /// Serve static assets from this directory instead of the copies built into the binary.
+    #[arg(long, env = "ASSETS_DIR")]
+    assets_dir: Option<std::path::PathBuf>,
+    /// Theme used when a request does not pick one.
+    #[arg(long, default_value = themes::DEFAULT_THEME, env = "THEME")]
+    theme: String,
+    /// Directory of extra themes, one subdirectory per theme.
+    #[arg(long, env = "THEMES_DIR")]
+    themes_dir: Option<std::path::PathBuf>,
+    /// Log output format.
+    #[arg(long, value_enum, default_value = "text", env = "LOG_FORMAT")]
+    log_format: LogFormat,
+    /// Export spans over OTLP/HTTP to this collector, e.g. http://localhost:4318.
+    #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
+    /// Service name reported on exported spans.
+    #[arg(long, default_value = "quote_server", env = "OTEL_SERVICE_NAME")]
+    otel_service_name: String,
+    /// Extra key=value resource attributes attached to exported spans.
+    #[arg(long, env = "OTEL_RESOURCE_ATTRIBUTES", value_delimiter = ',')]
+    otel_resource_attributes: Vec<ResourceAttribute>,
+    /// Serve /metrics on this separate admin address instead of the main listener.
+    #[arg(long, env = "METRICS_ADDR")]
+    metrics_addr: Option<SocketAddr>,
 }
 
 pub struct AppState {
     pub db: SqlitePool,
     pub jwt_keys: authjwt::JwtKeys,
     pub reg_key: String,
+    pub submissions_per_hour: i64,
+    pub rate_limiter: RateLimiter,
+    pub trusted_proxies: Vec<TrustedProxy>,
+    pub quote_cache_control: String,
+    pub require_if_match: bool,
+    pub idempotency_window: chrono::TimeDelta,
+    pub quote_index: QuoteIndex,
+    /// Texts of live quotes, split up for duplicate checks.
+    pub trigram_index: TrigramIndex,
+    pub random_sessions: RandomSessions,
+    pub metrics: PrometheusHandle,
+    /// Set once shutdown has begun, so /readyz fails while connections drain.
+    pub draining: Arc<AtomicBool>,
+    pub assets: Assets,
+    pub themes: Themes,
+    pub locales: Locales,
+    pub duplicates: DuplicatePolicy,
 }
 
-fn get_db_uri_from_args_or_env(args_db_uri: Option<&str>) -> Cow<str> {
+impl AppState {
+    /// Brings the quote and trigram indexes in line with a quote that was
+    /// just written.
+    pub async fn reindex(&self, quote_id: &str) {
+        if let Err(e) = self.quote_index.reload(&self.db, quote_id).await {
+            tracing::error!("Failed to reindex quote {}: {}", quote_id, e);
+        }
+        if let Err(e) = self.trigram_index.reload(&self.db, quote_id).await {
+            tracing::error!("Failed to reindex the text of quote {}: {}", quote_id, e);
+        }
+    }
+}
+
+fn get_db_uri_from_args_or_env(args_db_uri: Option<&str>) -> Cow<'_, str> {
     if let Some(uri) = args_db_uri {
         uri.into()
     } else if let Ok(uri) = std::env::var("DATABASE_URL") {
@@ -63,11 +213,29 @@ fn get_db_uri_from_args_or_env(args_db_uri: Option<&str>) -> Cow<str> {
 #[derive(OpenApi)]
 #[openapi(
     paths(
-        crate::api::get_quote_api, crate::api::get_tagged_quote_api, crate::api::get_random_quote_api, crate::api::register, crate::api::add_quote
+        crate::api::get_quote_api, crate::api::list_quotes, crate::api::get_tagged_quote_api, crate::api::get_random_quote_api, crate::api::register, crate::api::add_quote,
+        crate::api::update_quote, crate::api::delete_quote, crate::api::get_quote_history, crate::api::revert_quote,
+        crate::api::restore_quote, crate::api::get_trash,
+        crate::api::submit_quote, crate::api::list_submissions, crate::api::update_submission,
+        crate::api::approve_submission, crate::api::reject_submission,
+        crate::api::list_translations, crate::api::put_translation, crate::api::delete_translation,
+        crate::api::get_citations, crate::api::get_broken_links,
+        crate::api::get_votes, crate::api::put_vote, crate::api::delete_vote, crate::api::get_top_quotes
     ),
     components(
         schemas(
-            crate::quote::Quote, crate::quote::JsonQuote, crate::authjwt::Registration, crate::authjwt::AuthBody, crate::authjwt::AuthError, crate::authjwt::Claims,
+            crate::quote::Quote, crate::quote::JsonQuote, crate::quote::QuoteAudit, crate::quote::TrashedQuote,
+            crate::quote::Attribution, crate::quote::Medium, crate::quote::Verification,
+            crate::citation::QuoteSource, crate::citation::SourceKind, crate::linkcheck::BrokenLink,
+            crate::duplicate::DuplicateMatch,
+            crate::revision::QuoteRevision, crate::revision::RevisionAction, crate::revision::RevisionDiff,
+            crate::revision::FieldChange, crate::revision::RevertRequest,
+            crate::submission::Submission, crate::submission::SubmissionRequest, crate::submission::SubmissionStatus,
+            crate::submission::RejectRequest,
+            crate::translation::QuoteTranslation, crate::translation::TranslationRequest,
+            crate::vote::VoteDirection, crate::vote::VoteRequest, crate::vote::VoteTally,
+            crate::vote::TopWindow, crate::vote::TopQuote,
+            crate::authjwt::Registration, crate::authjwt::AuthBody, crate::authjwt::AuthError, crate::authjwt::Claims,
         )
     ),
     tags(
@@ -76,7 +244,7 @@ fn get_db_uri_from_args_or_env(args_db_uri: Option<&str>) -> Cow<str> {
 )]
 struct ApiDoc;
 
-async fn shutdown_signal() {
+async fn shutdown_signal(draining: Arc<AtomicBool>) {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -103,6 +271,7 @@ async fn shutdown_signal() {
         },
     }
 
+    draining.store(true, Ordering::Release);
     tokio::time::sleep(Duration::from_secs(2)).await;
     tracing::info!("Graceful shutdown complete.");
 }
@@ -110,6 +279,19 @@ async fn shutdown_signal() {
 async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
     dotenvy::dotenv().ok();
     let args = Args::parse();
+    let tracer_provider = match &args.otlp_endpoint {
+        Some(endpoint) => Some(telemetry::tracer_provider(&OtlpConfig {
+            endpoint: endpoint.clone(),
+            service_name: args.otel_service_name.clone(),
+            resource_attributes: args.otel_resource_attributes.clone(),
+        })?),
+        None => None,
+    };
+    logging::init(args.log_format, tracer_provider.as_ref());
+    let mut assets = Assets::load(args.assets_dir.as_deref())?;
+    let themes = Themes::load(args.themes_dir.as_deref(), &args.theme, &mut assets)?;
+    let locales = Locales::load()?;
+    templates::check_asset_links(&assets, &themes, &locales)?;
 
     let db_uri_str = get_db_uri_from_args_or_env(args.db_uri.as_deref());
     let db_uri = db_uri_str.as_ref();
@@ -133,44 +315,55 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
 
     let db_pool = SqlitePool::connect_with(connect_options).await?;
     sqlx::migrate!("./migrations").run(&db_pool).await?;
+    let fingerprinted = duplicate::backfill_fingerprints(&db_pool).await?;
+    if fingerprinted > 0 {
+        tracing::info!("Fingerprinted {} existing quotes", fingerprinted);
+    }
+    let renormalized = quote::renormalize_tags(&db_pool).await?;
+    if renormalized > 0 {
+        tracing::info!("Renormalized {} existing tags", renormalized);
+    }
 
     if let Some(path) = args.init_from {
         tracing::info!("Initializing database from: {:?}", path);
         let json_quotes_vec = read_quotes_from_file(path)?;
-        'outer_init_loop: for jq_item in json_quotes_vec {
+        let imported_at = chrono::Utc::now();
+        'outer_init_loop: for mut jq_item in json_quotes_vec {
+            if let Err(e) = jq_item.clean() {
+                tracing::error!("Skipping quote {}: {}", jq_item.id, e);
+                continue 'outer_init_loop;
+            }
             let (quote_data, tags_iter) = jq_item.to_quote();
+            let attribution = &quote_data.attribution;
+            let quote_fingerprint = duplicate::fingerprint(&quote_data.answer_who);
             let mut tx = db_pool.begin().await?;
 
             let insert_res = sqlx::query!(
-                "INSERT OR IGNORE INTO quotes (id, whos_there, answer_who, source) VALUES ($1, $2, $3, $4)",
-                quote_data.id, quote_data.whos_there, quote_data.answer_who, quote_data.source
+                "INSERT OR IGNORE INTO quotes (id, whos_there, answer_who, source, said_on, event, medium, verification,
+                    fingerprint, created_at, updated_at, created_by, updated_by)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10, $11, $11)",
+                quote_data.id, quote_data.whos_there, quote_data.answer_who, quote_data.source,
+                attribution.said_on, attribution.event, attribution.medium, attribution.verification,
+                quote_fingerprint, imported_at, IMPORT_AUTHOR
             )
             .execute(&mut *tx)
             .await;
 
-            if let Err(e) = insert_res {
-                tracing::error!("Failed to insert quote {}: {}", quote_data.id, e);
-                tx.rollback().await?;
-                continue 'outer_init_loop;
-            }
-
-            for tag_val in tags_iter {
-                let normalized_tag = tag_val.trim().to_lowercase();
-                if normalized_tag.is_empty() {
-                    continue;
+            let inserted = match insert_res {
+                Ok(res) => res.rows_affected() > 0,
+                Err(e) => {
+                    tracing::error!("Failed to insert quote {}: {}", quote_data.id, e);
+                    tx.rollback().await?;
+                    continue 'outer_init_loop;
                 }
+            };
 
-                let tag_res = sqlx::query!(
-                    "INSERT OR IGNORE INTO quote_tags (quote_id, tag) VALUES ($1, $2)",
-                    quote_data.id,
-                    normalized_tag
-                )
-                .execute(&mut *tx)
-                .await;
+            for tag_val in tags_iter {
+                let tag_res = quote::insert_tag(&mut tx, &quote_data.id, tag_val).await;
                 if let Err(e) = tag_res {
                     tracing::error!(
                         "Failed to insert tag '{}' for quote {}: {}",
-                        normalized_tag,
+                        tag_val,
                         quote_data.id,
                         e
                     );
@@ -178,6 +371,27 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
                     continue 'outer_init_loop;
                 }
             }
+            if inserted {
+                let sources_res =
+                    citation::replace_sources(&mut tx, &quote_data.id, &quote_data.sources).await;
+                if let Err(e) = sources_res {
+                    tracing::error!("Failed to insert sources for quote {}: {}", quote_data.id, e);
+                    tx.rollback().await?;
+                    continue 'outer_init_loop;
+                }
+                let rev_res = revision::record_revision(
+                    &mut tx,
+                    &quote_data.id,
+                    revision::RevisionAction::Create,
+                    IMPORT_AUTHOR,
+                )
+                .await;
+                if let Err(e) = rev_res {
+                    tracing::error!("Failed to record revision for quote {}: {}", quote_data.id, e);
+                    tx.rollback().await?;
+                    continue 'outer_init_loop;
+                }
+            }
             if let Err(e) = tx.commit().await {
                 tracing::error!(
                     "Failed to commit transaction for quote {}: {}",
@@ -189,6 +403,55 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
         tracing::info!("Database initialization complete.");
     }
 
+    if args.purge_trash {
+        let cutoff = chrono::Utc::now() - chrono::TimeDelta::days(args.trash_max_age_days);
+        let purged = quote::purge_trash_from_db(&db_pool, cutoff).await?;
+        println!("purged {} quotes deleted before {}", purged, cutoff);
+        shutdown_tracer(tracer_provider);
+        return Ok(());
+    }
+
+    if args.duplicate_report {
+        let clusters = duplicate::duplicate_clusters(&db_pool, args.duplicate_threshold).await?;
+        for cluster in &clusters {
+            println!(
+                "{} quotes, similarity {:.2} to {:.2}:",
+                cluster.quotes.len(),
+                cluster.min_similarity,
+                cluster.max_similarity
+            );
+            for (id, whos_there, answer_who) in &cluster.quotes {
+                println!("  {}  {}: {}", id, whos_there, answer_who);
+            }
+        }
+        println!("{} duplicate clusters", clusters.len());
+        shutdown_tracer(tracer_provider);
+        return Ok(());
+    }
+
+    let link_check_config = LinkCheckConfig {
+        interval: Duration::from_secs(args.link_check_interval),
+        concurrency: args.link_check_concurrency,
+        host_delay: Duration::from_millis(args.link_check_host_delay_ms),
+        timeout: Duration::from_secs(args.link_check_timeout),
+    };
+    let link_client = linkcheck::client(link_check_config.timeout)?;
+    if args.check_links {
+        let summary =
+            linkcheck::check_links(&db_pool, &link_client, &link_check_config, true).await?;
+        for link in linkcheck::get_broken_links_from_db(&db_pool).await? {
+            let status = link.status.map(|status| status.to_string());
+            let reason = link.error.or(status).unwrap_or_default();
+            println!("{} {} ({})", link.url, reason, link.quote_ids.join(", "));
+        }
+        println!("checked {} links, {} broken", summary.checked, summary.broken);
+        shutdown_tracer(tracer_provider);
+        return Ok(());
+    }
+    if args.link_check_interval > 0 {
+        linkcheck::spawn(db_pool.clone(), link_client, link_check_config);
+    }
+
     let jwt_keys = authjwt::make_jwt_keys().await.unwrap_or_else(|e| {
         tracing::error!("Failed to create JWT keys: {}", e);
         std::process::exit(1);
@@ -200,62 +463,138 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
             std::process::exit(1);
         });
 
+    let metrics_handle = metrics::install()?;
+    let draining = Arc::new(AtomicBool::new(false));
+    let quote_index = QuoteIndex::load(&db_pool, !args.no_quote_index, args.quote_cache_size).await?;
+    let trigram_index =
+        TrigramIndex::load(&db_pool, args.duplicates != DuplicateAction::Off).await?;
+
     let app_state = AppState {
         db: db_pool,
         jwt_keys,
         reg_key,
+        submissions_per_hour: args.submissions_per_hour,
+        rate_limiter: RateLimiter::new(RateLimits {
+            read: args.rate_limit_read,
+            write: args.rate_limit_write,
+            auth: args.rate_limit_auth,
+            submit: args.rate_limit_submit,
+        }),
+        trusted_proxies: args.trusted_proxies,
+        quote_cache_control: format!(
+            "public, max-age={}, s-maxage={}",
+            args.cache_max_age, args.cdn_max_age
+        ),
+        require_if_match: args.require_if_match,
+        idempotency_window: chrono::TimeDelta::seconds(args.idempotency_window),
+        quote_index,
+        trigram_index,
+        random_sessions: RandomSessions::default(),
+        metrics: metrics_handle,
+        draining: draining.clone(),
+        assets,
+        themes,
+        locales,
+        duplicates: DuplicatePolicy {
+            action: args.duplicates,
+            threshold: args.duplicate_threshold,
+        },
     };
     let shared_state = Arc::new(RwLock::new(app_state));
 
-    tracing_subscriber::registry()
-        .with(
-            EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "quote_server=debug,tower_http=info".into()),
-        )
-        .with(fmt::layer())
-        .init();
-
     let trace_layer = TraceLayer::new_for_http()
-        .make_span_with(DefaultMakeSpan::new().level(tracing::Level::INFO))
+        .make_span_with(logging::make_span)
         .on_response(DefaultOnResponse::new().level(tracing::Level::INFO));
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
         .allow_origin(Any);
-    let mime_favicon = "image/vnd.microsoft.icon".parse::<mime::Mime>().unwrap();
-    let mime_css = mime::TEXT_CSS_UTF_8;
 
     let openapi_document: utoipa::openapi::OpenApi = ApiDoc::openapi();
 
+    let metrics_route = Router::new().route("/metrics", get(metrics::metrics_handler));
+    let metrics_route = match args.metrics_addr {
+        Some(metrics_addr) => {
+            let admin = metrics_route.with_state(shared_state.clone());
+            let admin_listener = TcpListener::bind(metrics_addr).await?;
+            tracing::info!("Metrics listening on http://{}/metrics", metrics_addr);
+            let admin_draining = draining.clone();
+            tokio::spawn(async move {
+                let served = axum::serve(admin_listener, admin)
+                    .with_graceful_shutdown(shutdown_signal(admin_draining))
+                    .await;
+                if let Err(e) = served {
+                    tracing::error!("Metrics listener failed: {}", e);
+                }
+            });
+            Router::new()
+        }
+        None => metrics_route,
+    };
+
     let app = Router::new()
         .route("/", get(web::get_main_page_handler))
-        .route_service(
-            "/style.css",
-            ServeFile::new_with_mime("assets/static/style.css", &mime_css),
+        .route(
+            "/submit",
+            get(web::get_submit_page_handler).post(web::post_submit_handler),
         )
-        .route_service(
-            "/favicon.ico",
-            ServeFile::new_with_mime("assets/static/favicon.ico", &mime_favicon),
+        .route("/vote", post(web::post_vote_handler))
+        .route("/{locale}/", get(web::get_main_page_handler))
+        .route(
+            "/{locale}/submit",
+            get(web::get_submit_page_handler).post(web::post_submit_handler),
         )
+        .route("/{locale}/vote", post(web::post_vote_handler))
+        .route("/static/{file}", get(assets::get_static_asset))
+        .route("/favicon.ico", get(assets::get_favicon))
         .nest("/api/v1", api::router())
+        .merge(metrics_route)
         .merge(
             SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", openapi_document.clone()),
         )
         .merge(RapiDoc::new("/api-docs/openapi.json").path("/rapidoc"))
         .fallback(handler_404)
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            idempotency::idempotency,
+        ))
+        .layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            ratelimit::rate_limit,
+        ))
+        // Probes are added after the rate limit so they are never throttled.
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .layer(middleware::from_fn(metrics::track_requests))
+        .layer(middleware::from_fn(logging::record_path_fields))
         .layer(cors)
+        .layer(PropagateRequestIdLayer::x_request_id())
         .layer(trace_layer)
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
         .with_state(shared_state);
 
     let listener = TcpListener::bind(&format!("{}:{}", args.ip, args.port)).await?;
     tracing::info!("Quote server listening on http://{}:{}", args.ip, args.port);
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(draining))
+    .await?;
+    shutdown_tracer(tracer_provider);
 
     Ok(())
 }
 
+/// Flushes spans still buffered for export.
+fn shutdown_tracer(tracer_provider: Option<SdkTracerProvider>) {
+    if let Some(provider) = tracer_provider {
+        if let Err(e) = provider.shutdown() {
+            eprintln!("quote_server: failed to flush traces: {}", e);
+        }
+    }
+}
+
 async fn handler_404() -> impl IntoResponse {
     (StatusCode::NOT_FOUND, "Oops! Page not found.")
 }
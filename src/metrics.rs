@@ -0,0 +1,118 @@
+// metrics.rs
+use crate::AppState;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+const LATENCY_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// How often histogram samples are folded into their buckets between scrapes.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Installs the global Prometheus recorder. Duration metrics, named
+/// `*_seconds`, are exported as histograms.
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)?
+        .install_recorder()?;
+
+    let upkeep = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep.run_upkeep();
+        }
+    });
+    Ok(handle)
+}
+
+/// Counts requests and their latency per route and status. Requests that
+/// match no route are labelled `unmatched` so stray paths do not each get
+/// their own series.
+pub async fn track_requests(req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let started = Instant::now();
+    let response = next.run(req).await;
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!("http_request_duration_seconds", &labels)
+        .record(started.elapsed().as_secs_f64());
+    response
+}
+
+/// Records how long a `quote.rs` database function took when dropped.
+pub struct QueryTimer {
+    query: &'static str,
+    started: Instant,
+}
+
+impl QueryTimer {
+    pub fn start(query: &'static str) -> Self {
+        Self {
+            query,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        metrics::histogram!("db_query_duration_seconds", "query" => self.query)
+            .record(self.started.elapsed().as_secs_f64());
+    }
+}
+
+/// Refreshes the gauges that are read rather than counted.
+async fn update_gauges(state: &AppState) -> Result<(), sqlx::Error> {
+    let db = &state.db;
+    metrics::gauge!("db_pool_connections").set(db.size() as f64);
+    metrics::gauge!("db_pool_idle_connections").set(db.num_idle() as f64);
+    metrics::gauge!("db_pool_max_connections").set(db.options().get_max_connections() as f64);
+
+    let quotes = sqlx::query_scalar!("SELECT COUNT(*) FROM quotes WHERE deleted_at IS NULL;")
+        .fetch_one(db)
+        .await?;
+    let tags = sqlx::query_scalar!(
+        r#"SELECT COUNT(DISTINCT qt.tag_norm) as "count!: i64"
+        FROM quote_tags qt
+        JOIN quotes q ON q.id = qt.quote_id AND q.deleted_at IS NULL;"#
+    )
+    .fetch_one(db)
+    .await?;
+    metrics::gauge!("quotes").set(quotes as f64);
+    metrics::gauge!("tags").set(tags as f64);
+    Ok(())
+}
+
+pub async fn metrics_handler(State(app_state): State<Arc<RwLock<AppState>>>) -> Response {
+    let state_guard = app_state.read().await;
+    if let Err(e) = update_gauges(&state_guard).await {
+        tracing::warn!("Metrics: Failed to read quote counts: {}", e);
+    }
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state_guard.metrics.render(),
+    )
+        .into_response()
+}
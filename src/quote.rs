@@ -1,8 +1,16 @@
 // quote.rs
-use crate::error::QuoteAppError;
+use crate::citation::{self, QuoteSource};
+use crate::duplicate::{self, DuplicateAction, DuplicatePolicy, TrigramIndex};
+use crate::error::{QuoteAddError, QuoteAppError, QuoteWriteError};
+use crate::metrics::QueryTimer;
+use crate::revision::{self, RevisionAction};
+use crate::rng::QuoteRng;
+use crate::vote;
+use chrono::{DateTime, Utc};
+use roaring::RoaringBitmap;
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, SqlitePool};
-use std::collections::HashSet;
+use sqlx::{types::Json, FromRow, SqliteConnection, SqlitePool};
+use std::collections::{BTreeSet, HashSet};
 use std::path::Path;
 use utoipa::ToSchema;
 
@@ -11,8 +19,136 @@ pub struct JsonQuote {
     pub id: String,
     pub whos_there: String,
     pub answer_who: String,
-    pub tags: HashSet<String>,
+    pub tags: BTreeSet<String>,
     pub source: String,
+    #[serde(default)]
+    pub attribution: Attribution,
+    /// Citations, in the order they are listed.
+    #[serde(default)]
+    pub sources: Vec<QuoteSource>,
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub audit: QuoteAudit,
+    /// Bumped on every write; the `ETag` of a quote carries it for `If-Match`.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub version: i64,
+    /// Language of `answer_who` when a translation was served in place of
+    /// the original text.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only, example = "es")]
+    pub lang: Option<String>,
+}
+
+/// Who wrote a quote and when. Set by the server on every write and
+/// ignored when a client sends it back.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, Clone)]
+pub struct QuoteAudit {
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub created_by: Option<String>,
+    pub updated_by: Option<String>,
+}
+
+/// How a quote came to be said.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum Medium {
+    Interview,
+    PressConference,
+    Broadcast,
+    Speech,
+    Book,
+    Article,
+    Tweet,
+    Other,
+}
+
+impl Medium {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Medium::Interview => "interview",
+            Medium::PressConference => "press_conference",
+            Medium::Broadcast => "broadcast",
+            Medium::Speech => "speech",
+            Medium::Book => "book",
+            Medium::Article => "article",
+            Medium::Tweet => "tweet",
+            Medium::Other => "other",
+        }
+    }
+}
+
+/// How far a quote has been checked against its source.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type,
+)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum Verification {
+    #[default]
+    Unverified,
+    Verified,
+    Disputed,
+    Misattributed,
+}
+
+impl Verification {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verification::Unverified => "unverified",
+            Verification::Verified => "verified",
+            Verification::Disputed => "disputed",
+            Verification::Misattributed => "misattributed",
+        }
+    }
+}
+
+/// When, where and how a quote was said, as far as it is known.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Attribution {
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD`, as precise as is known.
+    #[schema(example = "2008-06-17")]
+    pub said_on: Option<String>,
+    #[schema(example = "2008 Finals postgame")]
+    pub event: Option<String>,
+    pub medium: Option<Medium>,
+    #[serde(default)]
+    pub verification: Verification,
+}
+
+/// Whether `date` is a year, year and month, or full date that exists.
+pub fn is_partial_date(date: &str) -> bool {
+    let bytes = date.as_bytes();
+    let digits = |range: std::ops::Range<usize>| bytes[range].iter().all(u8::is_ascii_digit);
+    match bytes.len() {
+        4 => digits(0..4),
+        7 => {
+            digits(0..4)
+                && bytes[4] == b'-'
+                && digits(5..7)
+                && (1..=12).contains(&date[5..7].parse::<u32>().unwrap_or(0))
+        }
+        10 => digits(0..4) && chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok(),
+        _ => false,
+    }
+}
+
+impl Attribution {
+    /// Trims the free-text fields, dropping blank ones, and checks the date.
+    pub fn clean(&mut self) -> Result<(), &'static str> {
+        for value in [&mut self.said_on, &mut self.event] {
+            *value = value
+                .take()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty());
+        }
+        if self.said_on.as_deref().is_some_and(|date| !is_partial_date(date)) {
+            return Err("said_on must be YYYY, YYYY-MM or YYYY-MM-DD");
+        }
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, FromRow, ToSchema)]
@@ -21,6 +157,65 @@ pub struct Quote {
     pub whos_there: String,
     pub answer_who: String,
     pub source: String,
+    #[sqlx(flatten)]
+    pub attribution: Attribution,
+    /// Kept in `quote_sources`.
+    #[sqlx(skip)]
+    pub sources: Vec<QuoteSource>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub created_by: Option<String>,
+    pub updated_by: Option<String>,
+    pub version: i64,
+}
+
+/// Author recorded for quotes loaded with `--init-from`.
+pub const IMPORT_AUTHOR: &str = "init-from";
+
+/// The form tags are matched in. Stored next to each tag in `tag_norm`.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Speakers are matched case-insensitively, like tags.
+pub fn normalize_speaker(speaker: &str) -> String {
+    speaker.trim().to_lowercase()
+}
+
+/// Restrictions on which quote a random pick may return. Tags and speaker
+/// are held in normalized form.
+#[derive(Debug, Default, Clone)]
+pub struct QuoteFilter {
+    pub tags: Vec<String>,
+    pub speaker: Option<String>,
+    pub max_len: Option<usize>,
+    pub exclude: HashSet<String>,
+    /// Quotes to skip by SQLite rowid, as remembered by random-quote sessions.
+    pub exclude_rows: RoaringBitmap,
+    /// Favour quotes by their votes instead of picking uniformly.
+    pub weighted: bool,
+}
+
+impl QuoteFilter {
+    pub fn new<'a, I>(tags: I, speaker: Option<&str>, max_len: Option<usize>) -> Self
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        let tags: BTreeSet<String> = tags
+            .map(normalize_tag)
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        Self {
+            tags: tags.into_iter().collect(),
+            speaker: speaker
+                .map(normalize_speaker)
+                .filter(|speaker| !speaker.is_empty()),
+            max_len,
+            exclude: HashSet::new(),
+            exclude_rows: RoaringBitmap::new(),
+            weighted: false,
+        }
+    }
 }
 
 pub fn read_quotes_from_file<P: AsRef<Path>>(
@@ -32,6 +227,12 @@ pub fn read_quotes_from_file<P: AsRef<Path>>(
 }
 
 impl JsonQuote {
+    /// Cleans the attribution and sources sent by a client or import file.
+    pub fn clean(&mut self) -> Result<(), &'static str> {
+        self.attribution.clean()?;
+        self.sources.iter_mut().try_for_each(QuoteSource::clean)
+    }
+
     pub fn new(quote: &Quote, tags: Vec<String>) -> Self {
         let tags_set = tags.into_iter().collect();
         Self {
@@ -40,6 +241,16 @@ impl JsonQuote {
             answer_who: quote.answer_who.clone(),
             tags: tags_set,
             source: quote.source.clone(),
+            attribution: quote.attribution.clone(),
+            sources: quote.sources.clone(),
+            audit: QuoteAudit {
+                created_at: quote.created_at,
+                updated_at: quote.updated_at,
+                created_by: quote.created_by.clone(),
+                updated_by: quote.updated_by.clone(),
+            },
+            version: quote.version,
+            lang: None,
         }
     }
 
@@ -49,110 +260,653 @@ impl JsonQuote {
             whos_there: self.whos_there.clone(),
             answer_who: self.answer_who.clone(),
             source: self.source.clone(),
+            attribution: self.attribution.clone(),
+            sources: self.sources.clone(),
+            created_at: self.audit.created_at,
+            updated_at: self.audit.updated_at,
+            created_by: self.audit.created_by.clone(),
+            updated_by: self.audit.updated_by.clone(),
+            version: self.version,
         };
         let tags_iter = self.tags.iter().map(String::as_str);
         (quote, tags_iter)
     }
 }
 
+struct QuoteRow {
+    id: String,
+    whos_there: String,
+    answer_who: String,
+    source: String,
+    said_on: Option<String>,
+    event: Option<String>,
+    medium: Option<Medium>,
+    verification: Verification,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+    created_by: Option<String>,
+    updated_by: Option<String>,
+    version: i64,
+    tags: Json<Vec<String>>,
+    sources: Json<Vec<QuoteSource>>,
+}
+
+impl QuoteRow {
+    fn into_parts(self) -> (Quote, Vec<String>) {
+        let quote = Quote {
+            id: self.id,
+            whos_there: self.whos_there,
+            answer_who: self.answer_who,
+            source: self.source,
+            attribution: Attribution {
+                said_on: self.said_on,
+                event: self.event,
+                medium: self.medium,
+                verification: self.verification,
+            },
+            sources: self.sources.0,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            created_by: self.created_by,
+            updated_by: self.updated_by,
+            version: self.version,
+        };
+        (quote, self.tags.0)
+    }
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_quote_by_id"))]
 pub async fn get_quote_by_id_from_db(
     db: &SqlitePool,
     quote_id: &str,
 ) -> Result<(Quote, Vec<String>), sqlx::Error> {
-    let quote = sqlx::query_as!(
-        Quote,
-        "SELECT id, whos_there, answer_who, source FROM quotes WHERE id = $1;",
+    let _timer = QueryTimer::start("get_quote_by_id");
+    let row = sqlx::query_as!(
+        QuoteRow,
+        r#"SELECT id, whos_there, answer_who, source,
+            said_on, event, medium as "medium: Medium", verification as "verification: Verification",
+            created_at as "created_at: DateTime<Utc>",
+            updated_at as "updated_at: DateTime<Utc>",
+            created_by, updated_by, version,
+            (SELECT json_group_array(tag) FROM quote_tags WHERE quote_id = quotes.id) as "tags!: Json<Vec<String>>",
+            (SELECT json_group_array(json_object('url', url, 'title', title, 'publisher', publisher,
+                    'accessed_on', accessed_on, 'kind', kind))
+                FROM (SELECT * FROM quote_sources WHERE quote_id = quotes.id ORDER BY position)) as "sources!: Json<Vec<QuoteSource>>"
+        FROM quotes WHERE id = $1 AND deleted_at IS NULL;"#,
         quote_id
     )
     .fetch_one(db)
     .await?;
 
-    let tags: Vec<String> =
-        sqlx::query_scalar!("SELECT tag FROM quote_tags WHERE quote_id = $1;", quote_id)
-            .fetch_all(db)
-            .await?;
+    Ok(row.into_parts())
+}
 
-    Ok((quote, tags))
+/// Restrictions on the quotes listed by the listing API. Tags and speaker
+/// are held in normalized form; dates are partial ISO dates.
+#[derive(Debug, Default)]
+pub struct QuoteListFilter {
+    pub tags: Vec<String>,
+    pub speaker: Option<String>,
+    /// Quotes said in or after this period.
+    pub said_from: Option<String>,
+    /// Quotes said in or before this period.
+    pub said_to: Option<String>,
+    /// Case-insensitive substring of the event.
+    pub event: Option<String>,
+    pub medium: Option<Medium>,
+    pub verification: Option<Verification>,
 }
 
-pub async fn get_random_quote_id_from_db(db: &SqlitePool) -> Result<String, sqlx::Error> {
-    sqlx::query_scalar!("SELECT id FROM quotes ORDER BY RANDOM() LIMIT 1;")
-        .fetch_one(db)
-        .await
+/// A page of quotes matching `filter`, ordered by id. Quotes with no date
+/// are left out when either date bound is given.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "list_quotes"))]
+pub async fn list_quotes_from_db(
+    db: &SqlitePool,
+    filter: &QuoteListFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<(Quote, Vec<String>)>, sqlx::Error> {
+    let _timer = QueryTimer::start("list_quotes");
+    let tags = Json(&filter.tags);
+    let rows = sqlx::query_as!(
+        QuoteRow,
+        r#"SELECT q.id, q.whos_there, q.answer_who, q.source,
+            q.said_on, q.event, q.medium as "medium: Medium", q.verification as "verification: Verification",
+            q.created_at as "created_at: DateTime<Utc>",
+            q.updated_at as "updated_at: DateTime<Utc>",
+            q.created_by, q.updated_by, q.version,
+            (SELECT json_group_array(tag) FROM quote_tags WHERE quote_id = q.id) as "tags!: Json<Vec<String>>",
+            (SELECT json_group_array(json_object('url', url, 'title', title, 'publisher', publisher,
+                    'accessed_on', accessed_on, 'kind', kind))
+                FROM (SELECT * FROM quote_sources WHERE quote_id = q.id ORDER BY position)) as "sources!: Json<Vec<QuoteSource>>"
+        FROM quotes q
+        WHERE q.deleted_at IS NULL
+            AND ($1 IS NULL OR LOWER(TRIM(q.whos_there)) = $1)
+            AND json_array_length($2) = (
+                SELECT COUNT(*) FROM quote_tags qt
+                WHERE qt.quote_id = q.id AND qt.tag_norm IN (SELECT value FROM json_each($2))
+            )
+            AND ($3 IS NULL OR q.said_on >= $3)
+            AND ($4 IS NULL OR SUBSTR(q.said_on, 1, LENGTH($4)) <= $4)
+            AND ($5 IS NULL OR INSTR(LOWER(q.event), LOWER($5)) > 0)
+            AND ($6 IS NULL OR q.medium = $6)
+            AND ($7 IS NULL OR q.verification = $7)
+        ORDER BY q.id
+        LIMIT $8 OFFSET $9;"#,
+        filter.speaker,
+        tags,
+        filter.said_from,
+        filter.said_to,
+        filter.event,
+        filter.medium,
+        filter.verification,
+        limit,
+        offset,
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rows.into_iter().map(QuoteRow::into_parts).collect())
 }
 
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_tagged_quote_id"))]
 pub async fn get_tagged_quote_id_from_db<'a, I>(
     db: &SqlitePool,
     search_tags: I,
+    rng: &mut QuoteRng,
 ) -> Result<Option<String>, sqlx::Error>
 where
     I: Iterator<Item = &'a str> + Send,
 {
-    let mut tx = db.begin().await?;
-    sqlx::query("DROP TABLE IF EXISTS temp_search_tags;")
-        .execute(&mut *tx)
-        .await?;
-    sqlx::query("CREATE TEMPORARY TABLE temp_search_tags (tag_query TEXT);")
-        .execute(&mut *tx)
-        .await?;
-    let mut has_tags = false;
-    for tag_item in search_tags {
-        let normalized_tag = tag_item.trim().to_lowercase();
-        if !normalized_tag.is_empty() {
-            sqlx::query("INSERT INTO temp_search_tags (tag_query) VALUES ($1);")
-                .bind(normalized_tag)
-                .execute(&mut *tx)
+    let _timer = QueryTimer::start("get_tagged_quote_id");
+    let search_tags: BTreeSet<String> = search_tags
+        .map(normalize_tag)
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    if search_tags.is_empty() {
+        return Ok(None);
+    }
+    let search_tags = Json(search_tags);
+
+    let ids = sqlx::query_scalar!(
+        r#"SELECT qt.quote_id
+        FROM quote_tags qt
+        JOIN quotes q ON q.id = qt.quote_id AND q.deleted_at IS NULL
+        WHERE qt.tag_norm IN (SELECT value FROM json_each($1))
+        GROUP BY qt.quote_id
+        HAVING COUNT(*) = json_array_length($1)
+        ORDER BY qt.quote_id;"#,
+        search_tags
+    )
+    .fetch_all(db)
+    .await?;
+    Ok(rng.choose(ids))
+}
+
+/// SQL counterpart of the in-memory index's filtered pick.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_filtered_quote_id"))]
+pub async fn get_filtered_quote_id_from_db(
+    db: &SqlitePool,
+    filter: &QuoteFilter,
+    rng: &mut QuoteRng,
+) -> Result<Option<String>, sqlx::Error> {
+    let _timer = QueryTimer::start("get_filtered_quote_id");
+    let tags = Json(&filter.tags);
+    let exclude = Json(&filter.exclude);
+    let exclude_rows = Json(filter.exclude_rows.iter().collect::<Vec<u32>>());
+    let max_len = filter.max_len.map(|max_len| max_len as i64);
+
+    let rows = sqlx::query!(
+        r#"SELECT q.id, q.upvotes, q.downvotes
+        FROM quotes q
+        WHERE q.deleted_at IS NULL
+            AND ($1 IS NULL OR LOWER(TRIM(q.whos_there)) = $1)
+            AND ($2 IS NULL OR LENGTH(q.answer_who) <= $2)
+            AND q.id NOT IN (SELECT value FROM json_each($3))
+            AND q.rowid NOT IN (SELECT value FROM json_each($5))
+            AND json_array_length($4) = (
+                SELECT COUNT(*) FROM quote_tags qt
+                WHERE qt.quote_id = q.id AND qt.tag_norm IN (SELECT value FROM json_each($4))
+            )
+        ORDER BY q.id;"#,
+        filter.speaker,
+        max_len,
+        exclude,
+        tags,
+        exclude_rows,
+    )
+    .fetch_all(db)
+    .await?;
+    if filter.weighted {
+        let weighted = rows
+            .into_iter()
+            .map(|row| (row.id, vote::popularity_weight(row.upvotes, row.downvotes)))
+            .collect();
+        return Ok(rng.choose_weighted(weighted));
+    }
+    Ok(rng.choose(rows.into_iter().map(|row| row.id).collect()))
+}
+
+/// The SQLite rowid of a live quote, which random-quote sessions use to
+/// remember it compactly.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_quote_row"))]
+pub async fn get_quote_row_from_db(
+    db: &SqlitePool,
+    quote_id: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    let _timer = QueryTimer::start("get_quote_row");
+    sqlx::query_scalar!(
+        r#"SELECT rowid as "row!: i64" FROM quotes WHERE id = $1 AND deleted_at IS NULL"#,
+        quote_id
+    )
+    .fetch_optional(db)
+    .await
+}
+
+/// Adds a new quote. Under `duplicates`, a quote resembling a live one in
+/// `known` is refused unless `force` is set, or added with a warning. Runs
+/// on the caller's connection so it can commit the quote with related
+/// writes.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "add_quote"))]
+pub async fn add_quote_to_db(
+    conn: &mut SqliteConnection,
+    quote: JsonQuote,
+    author: &str,
+    duplicates: &DuplicatePolicy,
+    known: &TrigramIndex,
+    force: bool,
+) -> Result<(), QuoteAddError> {
+    let _timer = QueryTimer::start("add_quote");
+    let now = Utc::now();
+
+    if duplicates.action != DuplicateAction::Off {
+        let matches =
+            duplicate::find_duplicates(&mut *conn, known, &quote.answer_who, duplicates.threshold)
                 .await?;
-            has_tags = true;
+        if !matches.is_empty() {
+            if duplicates.action == DuplicateAction::Reject && !force {
+                return Err(QuoteAddError::Duplicate(matches));
+            }
+            let ids: Vec<&str> = matches.iter().map(|m| m.id.as_str()).collect();
+            tracing::warn!("Quote {} resembles {}", quote.id, ids.join(", "));
         }
     }
 
-    if !has_tags {
-        tx.commit().await?;
-        return Ok(None);
+    let attribution = &quote.attribution;
+    let quote_fingerprint = duplicate::fingerprint(&quote.answer_who);
+    sqlx::query!(
+        "INSERT INTO quotes (id, whos_there, answer_who, source, said_on, event, medium, verification,
+            fingerprint, created_at, updated_at, created_by, updated_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10, $11, $11)",
+        quote.id,
+        quote.whos_there,
+        quote.answer_who,
+        quote.source,
+        attribution.said_on,
+        attribution.event,
+        attribution.medium,
+        attribution.verification,
+        quote_fingerprint,
+        now,
+        author,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    for tag in &quote.tags {
+        insert_tag(&mut *conn, &quote.id, tag).await?;
     }
+    citation::replace_sources(&mut *conn, &quote.id, &quote.sources).await?;
 
-    let query_str = "
-        SELECT qt.quote_id
-        FROM quote_tags qt
-        JOIN temp_search_tags tst ON LOWER(qt.tag) = tst.tag_query
-        GROUP BY qt.quote_id
-        HAVING COUNT(DISTINCT tst.tag_query) = (SELECT COUNT(*) FROM temp_search_tags)
-        ORDER BY RANDOM()
-        LIMIT 1;
-    ";
+    revision::record_revision(conn, &quote.id, RevisionAction::Create, author).await?;
+    Ok(())
+}
+
+/// Adds `tag` to a quote unless it is blank or the quote already has it in
+/// some spelling. The first spelling is the one shown.
+pub async fn insert_tag(
+    conn: &mut SqliteConnection,
+    quote_id: &str,
+    tag: &str,
+) -> Result<(), sqlx::Error> {
+    let tag = tag.trim();
+    let tag_norm = normalize_tag(tag);
+    if tag_norm.is_empty() {
+        return Ok(());
+    }
+    sqlx::query!(
+        "INSERT OR IGNORE INTO quote_tags (quote_id, tag, tag_norm) VALUES ($1, $2, $3)",
+        quote_id,
+        tag,
+        tag_norm,
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Brings `tag_norm` in line with [`normalize_tag`] for tags the SQL
+/// backfill could not fold, those with characters outside printable ASCII.
+/// Tags of one quote that now normalize alike are merged.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "renormalize_tags"))]
+pub async fn renormalize_tags(db: &SqlitePool) -> Result<usize, sqlx::Error> {
+    let _timer = QueryTimer::start("renormalize_tags");
+    let mut tx = db.begin().await?;
+    let rows = sqlx::query!(
+        r#"SELECT id as "id!: i64", quote_id, tag, tag_norm FROM quote_tags
+        WHERE tag GLOB '*[^ -~]*' ORDER BY id"#
+    )
+    .fetch_all(&mut *tx)
+    .await?;
 
-    let result_id: Option<String> = sqlx::query_scalar(query_str)
+    let mut changed = 0;
+    for row in rows {
+        let tag = row.tag.trim();
+        let tag_norm = normalize_tag(tag);
+        if tag == row.tag && tag_norm == row.tag_norm {
+            continue;
+        }
+        let taken = sqlx::query_scalar!(
+            "SELECT id FROM quote_tags WHERE quote_id = $1 AND tag_norm = $2 AND id != $3",
+            row.quote_id,
+            tag_norm,
+            row.id
+        )
         .fetch_optional(&mut *tx)
         .await?;
-
+        // The earliest spelling of a tag is the one kept.
+        if tag_norm.is_empty() || taken.is_some_and(|taken| taken < row.id) {
+            sqlx::query!("DELETE FROM quote_tags WHERE id = $1", row.id)
+                .execute(&mut *tx)
+                .await?;
+        } else {
+            if let Some(taken) = taken {
+                sqlx::query!("DELETE FROM quote_tags WHERE id = $1", taken)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            sqlx::query!(
+                "UPDATE quote_tags SET tag = $2, tag_norm = $3 WHERE id = $1",
+                row.id,
+                tag,
+                tag_norm
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        changed += 1;
+    }
     tx.commit().await?;
-    Ok(result_id)
+    Ok(changed)
+}
+
+async fn replace_tags<'a, I>(
+    conn: &mut SqliteConnection,
+    quote_id: &str,
+    tags: I,
+) -> Result<(), sqlx::Error>
+where
+    I: Iterator<Item = &'a String>,
+{
+    sqlx::query!("DELETE FROM quote_tags WHERE quote_id = $1", quote_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for tag in tags {
+        insert_tag(conn, quote_id, tag).await?;
+    }
+
+    Ok(())
+}
+
+/// Works out why a conditional write touched no rows.
+async fn write_failure(conn: &mut SqliteConnection, quote_id: &str) -> QuoteWriteError {
+    match sqlx::query_scalar!(
+        "SELECT version FROM quotes WHERE id = $1 AND deleted_at IS NULL",
+        quote_id
+    )
+    .fetch_optional(conn)
+    .await
+    {
+        Ok(Some(_)) => QuoteWriteError::PreconditionFailed,
+        Ok(None) => QuoteWriteError::NotFound,
+        Err(e) => e.into(),
+    }
 }
 
-pub async fn add_quote_to_db(db: &SqlitePool, quote: JsonQuote) -> Result<(), sqlx::Error> {
+/// Overwrites the text, sources, attribution and tags of an existing quote. The id in
+/// `quote` is ignored in favour of `quote_id`. With `expected_version` the
+/// write only happens if nobody else has written the quote in between.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "update_quote"))]
+pub async fn update_quote_in_db(
+    db: &SqlitePool,
+    quote_id: &str,
+    quote: JsonQuote,
+    author: &str,
+    expected_version: Option<i64>,
+) -> Result<(), QuoteWriteError> {
+    let _timer = QueryTimer::start("update_quote");
     let mut tx = db.begin().await?;
+    let now = Utc::now();
 
-    sqlx::query!(
-        "INSERT INTO quotes (id, whos_there, answer_who, source) VALUES ($1, $2, $3, $4)",
-        quote.id,
+    let attribution = &quote.attribution;
+    let quote_fingerprint = duplicate::fingerprint(&quote.answer_who);
+    let updated = sqlx::query!(
+        "UPDATE quotes SET whos_there = $2, answer_who = $3, source = $4, updated_at = $5, updated_by = $6,
+            said_on = $8, event = $9, medium = $10, verification = $11, fingerprint = $12, version = version + 1
+        WHERE id = $1 AND deleted_at IS NULL AND ($7 IS NULL OR version = $7)",
+        quote_id,
         quote.whos_there,
         quote.answer_who,
         quote.source,
+        now,
+        author,
+        expected_version,
+        attribution.said_on,
+        attribution.event,
+        attribution.medium,
+        attribution.verification,
+        quote_fingerprint,
+    )
+    .execute(&mut *tx)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(write_failure(&mut tx, quote_id).await);
+    }
+
+    replace_tags(&mut tx, quote_id, quote.tags.iter()).await?;
+    citation::replace_sources(&mut tx, quote_id, &quote.sources).await?;
+    revision::record_revision(&mut tx, quote_id, RevisionAction::Update, author).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Moves a quote to the trash. Its row and tags are kept until purged.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "delete_quote"))]
+pub async fn delete_quote_from_db(
+    db: &SqlitePool,
+    quote_id: &str,
+    author: &str,
+    expected_version: Option<i64>,
+) -> Result<(), QuoteWriteError> {
+    let _timer = QueryTimer::start("delete_quote");
+    let mut tx = db.begin().await?;
+    let now = Utc::now();
+
+    let deleted = sqlx::query!(
+        "UPDATE quotes SET deleted_at = $2, deleted_by = $3, version = version + 1
+        WHERE id = $1 AND deleted_at IS NULL AND ($4 IS NULL OR version = $4)",
+        quote_id,
+        now,
+        author,
+        expected_version,
+    )
+    .execute(&mut *tx)
+    .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(write_failure(&mut tx, quote_id).await);
+    }
+
+    revision::record_revision(&mut tx, quote_id, RevisionAction::Delete, author).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Takes a quote back out of the trash. With `expected_version` it only
+/// happens if nobody else has written the quote since that version.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "restore_quote"))]
+pub async fn restore_quote_in_db(
+    db: &SqlitePool,
+    quote_id: &str,
+    author: &str,
+    expected_version: Option<i64>,
+) -> Result<(), QuoteWriteError> {
+    let _timer = QueryTimer::start("restore_quote");
+    let mut tx = db.begin().await?;
+    let now = Utc::now();
+
+    let restored = sqlx::query!(
+        "UPDATE quotes SET deleted_at = NULL, deleted_by = NULL, updated_at = $2, updated_by = $3,
+            version = version + 1
+        WHERE id = $1 AND deleted_at IS NOT NULL AND ($4 IS NULL OR version = $4)",
+        quote_id,
+        now,
+        author,
+        expected_version,
     )
     .execute(&mut *tx)
     .await?;
+    if restored.rows_affected() == 0 {
+        let trashed = sqlx::query_scalar!(
+            "SELECT version FROM quotes WHERE id = $1 AND deleted_at IS NOT NULL",
+            quote_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        return Err(match trashed {
+            Some(_) => QuoteWriteError::PreconditionFailed,
+            None => QuoteWriteError::NotFound,
+        });
+    }
+
+    revision::record_revision(&mut tx, quote_id, RevisionAction::Restore, author).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TrashedQuote {
+    pub id: String,
+    pub whos_there: String,
+    pub answer_who: String,
+    pub source: String,
+    pub deleted_at: DateTime<Utc>,
+    pub deleted_by: Option<String>,
+    pub version: i64,
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_trash"))]
+pub async fn get_trash_from_db(db: &SqlitePool) -> Result<Vec<TrashedQuote>, sqlx::Error> {
+    let _timer = QueryTimer::start("get_trash");
+    sqlx::query_as!(
+        TrashedQuote,
+        r#"SELECT id, whos_there, answer_who, source,
+            deleted_at as "deleted_at!: DateTime<Utc>", deleted_by, version
+        FROM quotes WHERE deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC;"#
+    )
+    .fetch_all(db)
+    .await
+}
+
+/// Permanently removes quotes that went into the trash before `cutoff`.
+/// Their revision history is kept.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "purge_trash"))]
+pub async fn purge_trash_from_db(
+    db: &SqlitePool,
+    cutoff: DateTime<Utc>,
+) -> Result<u64, sqlx::Error> {
+    let _timer = QueryTimer::start("purge_trash");
+    let purged = sqlx::query!(
+        "DELETE FROM quotes WHERE deleted_at IS NOT NULL AND deleted_at < $1",
+        cutoff
+    )
+    .execute(db)
+    .await?;
+
+    Ok(purged.rows_affected())
+}
+
+/// Restores the content of `quote_id` to what it was at `revision_no`,
+/// taking it out of the trash or recreating it if it has been purged.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "revert_quote"))]
+pub async fn revert_quote_in_db(
+    db: &SqlitePool,
+    quote_id: &str,
+    revision_no: i64,
+    author: &str,
+    expected_version: Option<i64>,
+) -> Result<(), QuoteWriteError> {
+    let _timer = QueryTimer::start("revert_quote");
+    let mut tx = db.begin().await?;
+    let target = revision::get_revision_from_db(&mut tx, quote_id, revision_no).await?;
+    let attribution = target.attribution();
+    let quote_fingerprint = duplicate::fingerprint(&target.answer_who);
+    let now = Utc::now();
 
-    for tag in quote.tags {
+    let updated = sqlx::query!(
+        "UPDATE quotes SET whos_there = $2, answer_who = $3, source = $4, updated_at = $5, updated_by = $6,
+            said_on = $8, event = $9, medium = $10, verification = $11, fingerprint = $12,
+            deleted_at = NULL, deleted_by = NULL, version = version + 1
+        WHERE id = $1 AND ($7 IS NULL OR version = $7)",
+        quote_id,
+        target.whos_there,
+        target.answer_who,
+        target.source,
+        now,
+        author,
+        expected_version,
+        attribution.said_on,
+        attribution.event,
+        attribution.medium,
+        attribution.verification,
+        quote_fingerprint,
+    )
+    .execute(&mut *tx)
+    .await?;
+    if updated.rows_affected() == 0 {
+        let exists = sqlx::query_scalar!("SELECT version FROM quotes WHERE id = $1", quote_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .is_some();
+        if exists || expected_version.is_some() {
+            return Err(QuoteWriteError::PreconditionFailed);
+        }
         sqlx::query!(
-            "INSERT INTO quote_tags (quote_id, tag) VALUES ($1, $2)",
-            quote.id,
-            tag,
+            "INSERT INTO quotes (id, whos_there, answer_who, source, said_on, event, medium, verification,
+                fingerprint, created_at, updated_at, created_by, updated_by)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $10, $11, $11)",
+            quote_id,
+            target.whos_there,
+            target.answer_who,
+            target.source,
+            attribution.said_on,
+            attribution.event,
+            attribution.medium,
+            attribution.verification,
+            quote_fingerprint,
+            now,
+            author,
         )
         .execute(&mut *tx)
         .await?;
     }
 
+    replace_tags(&mut tx, quote_id, target.tags.iter()).await?;
+    citation::replace_sources(&mut tx, quote_id, &target.sources()).await?;
+    revision::record_revision(&mut tx, quote_id, RevisionAction::Revert, author).await?;
     tx.commit().await?;
 
     Ok(())
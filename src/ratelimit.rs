@@ -0,0 +1,338 @@
+// ratelimit.rs
+use crate::authjwt;
+use crate::logging;
+use crate::AppState;
+use axum::{
+    extract::{ConnectInfo, FromRef, FromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Buckets are swept once the table grows past this many keys.
+const SWEEP_THRESHOLD: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    Read,
+    Write,
+    Auth,
+    Submit,
+}
+
+impl RouteGroup {
+    pub fn classify(method: &Method, path: &str) -> Self {
+        if path == "/api/v1/register" {
+            RouteGroup::Auth
+        } else if *method == Method::POST && (path == "/api/v1/submissions" || path == "/submit") {
+            RouteGroup::Submit
+        } else if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+            RouteGroup::Read
+        } else {
+            RouteGroup::Write
+        }
+    }
+}
+
+/// `requests` per `per`, written `N/SECONDS` on the command line, or `off`.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("off") {
+            return Ok(RateLimit {
+                requests: 0,
+                per: Duration::ZERO,
+            });
+        }
+        let (requests, secs) = s
+            .split_once('/')
+            .ok_or_else(|| format!("expected N/SECONDS or off, got {:?}", s))?;
+        let requests: u32 = requests.trim().parse().map_err(|e| format!("{}", e))?;
+        let secs: u64 = secs.trim().parse().map_err(|e| format!("{}", e))?;
+        if requests == 0 || secs == 0 {
+            return Err("requests and seconds must be positive".to_string());
+        }
+        Ok(RateLimit {
+            requests,
+            per: Duration::from_secs(secs),
+        })
+    }
+}
+
+impl RateLimit {
+    fn is_off(&self) -> bool {
+        self.requests == 0
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        f64::from(self.requests) / self.per.as_secs_f64()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimits {
+    pub read: RateLimit,
+    pub write: RateLimit,
+    pub auth: RateLimit,
+    pub submit: RateLimit,
+}
+
+impl RateLimits {
+    fn for_group(&self, group: RouteGroup) -> RateLimit {
+        match group {
+            RouteGroup::Read => self.read,
+            RouteGroup::Write => self.write,
+            RouteGroup::Auth => self.auth,
+            RouteGroup::Submit => self.submit,
+        }
+    }
+}
+
+/// A proxy address or CIDR block whose `X-Forwarded-For` header we believe.
+#[derive(Clone, Copy, Debug)]
+pub struct TrustedProxy {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl FromStr for TrustedProxy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let network: IpAddr = addr.trim().parse().map_err(|e| format!("{}: {}", s, e))?;
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix {
+            Some(p) => p.trim().parse::<u8>().map_err(|e| format!("{}: {}", s, e))?,
+            None => max_len,
+        };
+        if prefix_len > max_len {
+            return Err(format!("{}: prefix length out of range", s));
+        }
+        Ok(TrustedProxy {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl TrustedProxy {
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix_len)).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix_len)).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Resolves the address of the client behind any trusted proxies. The
+/// `X-Forwarded-For` chain is walked right to left and the first hop that
+/// is not a trusted proxy wins.
+pub fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &[TrustedProxy]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted.iter().any(|proxy| proxy.contains(ip));
+    if !is_trusted(peer) {
+        return peer;
+    }
+
+    let mut client = peer;
+    let hops = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for hop in hops.iter().rev() {
+        match hop.trim().parse::<IpAddr>() {
+            Ok(ip) => {
+                client = ip;
+                if !is_trusted(ip) {
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+/// The resolved client address, honouring trusted proxies.
+#[derive(Clone, Copy, Debug)]
+pub struct ClientIp(pub IpAddr);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    Arc<RwLock<AppState>>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(client) = parts.extensions.get::<ClientIp>() {
+            return Ok(*client);
+        }
+        let ConnectInfo(peer) = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .copied()
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let app_state = Arc::<RwLock<AppState>>::from_ref(state);
+        let app_state_reader = app_state.read().await;
+        Ok(ClientIp(client_ip(
+            peer.ip(),
+            &parts.headers,
+            &app_state_reader.trusted_proxies,
+        )))
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+enum Decision {
+    Allowed { remaining: u32, reset: u64 },
+    Limited { retry_after: u64 },
+}
+
+/// Token buckets for every (route group, client) pair.
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Mutex<HashMap<(RouteGroup, String), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn check(&self, group: RouteGroup, key: String, limit: RateLimit) -> Decision {
+        let now = Instant::now();
+        let capacity = f64::from(limit.requests);
+        let rate = limit.refill_per_sec();
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > SWEEP_THRESHOLD {
+            let limits = self.limits;
+            buckets.retain(|(group, _), bucket| {
+                let idle = now.duration_since(bucket.updated);
+                idle < limits.for_group(*group).per
+            });
+        }
+
+        let bucket = buckets.entry((group, key)).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Decision::Allowed {
+                remaining: bucket.tokens.floor() as u32,
+                reset: ((capacity - bucket.tokens) / rate).ceil() as u64,
+            }
+        } else {
+            Decision::Limited {
+                retry_after: ((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64,
+            }
+        }
+    }
+}
+
+fn set_header(headers: &mut HeaderMap, name: &'static str, value: impl ToString) {
+    if let Ok(value) = HeaderValue::from_str(&value.to_string()) {
+        headers.insert(name, value);
+    }
+}
+
+/// Middleware applying the limit of the request's route group. Requests
+/// carrying a valid token are counted against their subject, everything
+/// else against the client address.
+pub async fn rate_limit(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let group = RouteGroup::classify(request.method(), request.uri().path());
+
+    let (client, subject, decision, limit) = {
+        let app_reader = app_state.read().await;
+        let client = client_ip(peer.ip(), request.headers(), &app_reader.trusted_proxies);
+        let subject = authjwt::bearer_subject(request.headers(), &app_reader.jwt_keys);
+        if let Some(sub) = &subject {
+            logging::record_subject(sub);
+        }
+
+        let limiter = &app_reader.rate_limiter;
+        let limit = limiter.limits.for_group(group);
+        let decision = if limit.is_off() {
+            None
+        } else {
+            let key = match &subject {
+                Some(sub) => format!("sub:{}", sub),
+                None => format!("ip:{}", client),
+            };
+            Some(limiter.check(group, key, limit))
+        };
+        (client, subject, decision, limit)
+    };
+    request.extensions_mut().insert(ClientIp(client));
+
+    match decision {
+        None => next.run(request).await,
+        Some(Decision::Allowed { remaining, reset }) => {
+            let mut response = next.run(request).await;
+            let headers = response.headers_mut();
+            set_header(headers, "ratelimit-limit", limit.requests);
+            set_header(headers, "ratelimit-remaining", remaining);
+            set_header(headers, "ratelimit-reset", reset);
+            response
+        }
+        Some(Decision::Limited { retry_after }) => {
+            tracing::warn!(
+                "Rate limit exceeded for {:?} by {}",
+                group,
+                subject.as_deref().unwrap_or(&client.to_string())
+            );
+            let body = axum::Json(serde_json::json!({ "error": "Too many requests." }));
+            let mut response = (StatusCode::TOO_MANY_REQUESTS, body).into_response();
+            let headers = response.headers_mut();
+            set_header(headers, "retry-after", retry_after);
+            set_header(headers, "ratelimit-limit", limit.requests);
+            set_header(headers, "ratelimit-remaining", 0);
+            set_header(headers, "ratelimit-reset", retry_after);
+            response
+        }
+    }
+}
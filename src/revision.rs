@@ -0,0 +1,236 @@
+// revision.rs
+use crate::citation::QuoteSource;
+use crate::quote::Attribution;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, SqliteConnection, SqlitePool};
+use std::collections::BTreeSet;
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum RevisionAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    Revert,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: String,
+}
+
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct RevisionDiff {
+    pub fields: Vec<FieldChange>,
+    pub tags_added: Vec<String>,
+    pub tags_removed: Vec<String>,
+    /// Source URLs, compared like tags.
+    pub sources_added: Vec<String>,
+    pub sources_removed: Vec<String>,
+}
+
+/// One immutable snapshot of a quote, with the changes relative to the
+/// snapshot before it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuoteRevision {
+    pub revision: i64,
+    pub action: RevisionAction,
+    pub author: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub whos_there: String,
+    pub answer_who: String,
+    pub source: String,
+    pub tags: Vec<String>,
+    pub attribution: Attribution,
+    pub sources: Vec<QuoteSource>,
+    pub changes: RevisionDiff,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RevertRequest {
+    #[schema(example = 1)]
+    pub revision: i64,
+}
+
+pub struct RevisionRow {
+    pub revision: i64,
+    pub action: RevisionAction,
+    pub whos_there: String,
+    pub answer_who: String,
+    pub source: String,
+    pub tags: Json<Vec<String>>,
+    pub attribution: Option<Json<Attribution>>,
+    pub sources: Option<Json<Vec<QuoteSource>>>,
+    pub author: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RevisionRow {
+    /// Revisions from before attribution was recorded have none.
+    pub fn attribution(&self) -> Attribution {
+        self.attribution
+            .as_ref()
+            .map(|attribution| attribution.0.clone())
+            .unwrap_or_default()
+    }
+
+    /// Likewise for sources.
+    pub fn sources(&self) -> Vec<QuoteSource> {
+        self.sources
+            .as_ref()
+            .map(|sources| sources.0.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Snapshots the current row and tags of `quote_id` as the next revision.
+/// Does nothing if the quote does not exist.
+pub async fn record_revision(
+    conn: &mut SqliteConnection,
+    quote_id: &str,
+    action: RevisionAction,
+    author: &str,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    sqlx::query!(
+        "INSERT INTO quote_revisions (quote_id, revision, action, whos_there, answer_who, source, tags, attribution, sources, author, created_at)
+        SELECT q.id,
+            COALESCE((SELECT MAX(revision) FROM quote_revisions WHERE quote_id = q.id), 0) + 1,
+            $2, q.whos_there, q.answer_who, q.source,
+            (SELECT json_group_array(tag) FROM (SELECT tag FROM quote_tags WHERE quote_id = q.id ORDER BY tag)),
+            json_object('said_on', q.said_on, 'event', q.event, 'medium', q.medium, 'verification', q.verification),
+            (SELECT json_group_array(json_object('url', url, 'title', title, 'publisher', publisher,
+                    'accessed_on', accessed_on, 'kind', kind))
+                FROM (SELECT * FROM quote_sources WHERE quote_id = q.id ORDER BY position)),
+            $3, $4
+        FROM quotes q WHERE q.id = $1",
+        quote_id,
+        action,
+        author,
+        now,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_revision_from_db(
+    conn: &mut SqliteConnection,
+    quote_id: &str,
+    revision: i64,
+) -> Result<RevisionRow, sqlx::Error> {
+    sqlx::query_as!(
+        RevisionRow,
+        r#"SELECT revision, action as "action: RevisionAction",
+            whos_there, answer_who, source,
+            tags as "tags: Json<Vec<String>>", attribution as "attribution: Json<Attribution>",
+            sources as "sources: Json<Vec<QuoteSource>>", author, created_at as "created_at: DateTime<Utc>"
+        FROM quote_revisions WHERE quote_id = $1 AND revision = $2;"#,
+        quote_id,
+        revision
+    )
+    .fetch_one(conn)
+    .await
+}
+
+pub async fn get_history_from_db(
+    db: &SqlitePool,
+    quote_id: &str,
+) -> Result<Vec<QuoteRevision>, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        RevisionRow,
+        r#"SELECT revision, action as "action: RevisionAction",
+            whos_there, answer_who, source,
+            tags as "tags: Json<Vec<String>>", attribution as "attribution: Json<Attribution>",
+            sources as "sources: Json<Vec<QuoteSource>>", author, created_at as "created_at: DateTime<Utc>"
+        FROM quote_revisions WHERE quote_id = $1 ORDER BY revision;"#,
+        quote_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut history = Vec::with_capacity(rows.len());
+    let mut previous: Option<&RevisionRow> = None;
+    for row in &rows {
+        history.push(QuoteRevision {
+            revision: row.revision,
+            action: row.action,
+            author: row.author.clone(),
+            created_at: row.created_at,
+            whos_there: row.whos_there.clone(),
+            answer_who: row.answer_who.clone(),
+            source: row.source.clone(),
+            tags: row.tags.0.clone(),
+            attribution: row.attribution(),
+            sources: row.sources(),
+            changes: diff_revisions(previous, row),
+        });
+        previous = Some(row);
+    }
+
+    Ok(history)
+}
+
+fn diff_revisions(old: Option<&RevisionRow>, new: &RevisionRow) -> RevisionDiff {
+    let mut fields = Vec::new();
+    let pairs = [
+        ("whos_there", old.map(|o| &o.whos_there), &new.whos_there),
+        ("answer_who", old.map(|o| &o.answer_who), &new.answer_who),
+        ("source", old.map(|o| &o.source), &new.source),
+    ];
+    for (field, old_value, new_value) in pairs {
+        if old_value != Some(new_value) {
+            fields.push(FieldChange {
+                field: field.to_string(),
+                old: old_value.cloned(),
+                new: new_value.clone(),
+            });
+        }
+    }
+
+    // Attribution fields may be unset, so unlike the ones above they only
+    // count as changed when set to something different.
+    let attribution_fields = |row: &RevisionRow| {
+        let attribution = row.attribution();
+        [
+            ("said_on", attribution.said_on),
+            ("event", attribution.event),
+            ("medium", attribution.medium.map(|medium| medium.as_str().to_string())),
+            ("verification", Some(attribution.verification.as_str().to_string())),
+        ]
+    };
+    let old_values = old.map(attribution_fields);
+    for (i, (field, new_value)) in attribution_fields(new).into_iter().enumerate() {
+        let old_value = old_values.as_ref().and_then(|values| values[i].1.clone());
+        if old_value != new_value {
+            fields.push(FieldChange {
+                field: field.to_string(),
+                old: old_value,
+                new: new_value.unwrap_or_default(),
+            });
+        }
+    }
+
+    let old_tags: BTreeSet<&String> = old.map(|o| o.tags.iter().collect()).unwrap_or_default();
+    let new_tags: BTreeSet<&String> = new.tags.iter().collect();
+    let source_urls = |row: &RevisionRow| -> BTreeSet<String> {
+        row.sources().into_iter().map(|source| source.url).collect()
+    };
+    let old_sources = old.map(source_urls).unwrap_or_default();
+    let new_sources = source_urls(new);
+
+    RevisionDiff {
+        fields,
+        tags_added: new_tags.difference(&old_tags).map(|t| t.to_string()).collect(),
+        tags_removed: old_tags.difference(&new_tags).map(|t| t.to_string()).collect(),
+        sources_added: new_sources.difference(&old_sources).cloned().collect(),
+        sources_removed: old_sources.difference(&new_sources).cloned().collect(),
+    }
+}
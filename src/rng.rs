@@ -0,0 +1,55 @@
+// rng.rs
+
+/// Response header carrying the seed a random pick was made with.
+pub const SEED_HEADER: &str = "x-quote-seed";
+
+/// Source of every random quote pick. Each request gets its own generator
+/// from a seed, so sending the same seed against the same set of quotes
+/// replays the same pick.
+pub struct QuoteRng {
+    seed: u64,
+    rng: fastrand::Rng,
+}
+
+impl QuoteRng {
+    /// Seeds with `seed`, or with a freshly drawn one if there is none.
+    pub fn new(seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| fastrand::u64(..));
+        Self {
+            seed,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// A uniformly chosen index below `count`, or `None` if `count` is zero.
+    pub fn index(&mut self, count: u64) -> Option<u64> {
+        (count > 0).then(|| self.rng.u64(..count))
+    }
+
+    /// Takes a uniformly chosen item out of `items`.
+    pub fn choose<T>(&mut self, mut items: Vec<T>) -> Option<T> {
+        let nth = self.index(items.len() as u64)?;
+        Some(items.swap_remove(nth as usize))
+    }
+
+    /// Takes an item out of `items`, each with a chance proportional to its
+    /// weight. Items must have positive weights.
+    pub fn choose_weighted<T>(&mut self, mut items: Vec<(T, f64)>) -> Option<T> {
+        let last = items.len().checked_sub(1)?;
+        let total: f64 = items.iter().map(|(_, weight)| weight).sum();
+        let mut target = self.rng.f64() * total;
+        // Rounding can leave a sliver past the last item; it goes to that item.
+        let nth = items
+            .iter()
+            .position(|(_, weight)| {
+                target -= weight;
+                target < 0.0
+            })
+            .unwrap_or(last);
+        Some(items.swap_remove(nth).0)
+    }
+}
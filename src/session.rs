@@ -0,0 +1,111 @@
+// session.rs
+use crate::index::QuoteIndex;
+use crate::quote::QuoteFilter;
+use crate::rng::QuoteRng;
+use lru::LruCache;
+use roaring::RoaringBitmap;
+use sqlx::SqlitePool;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+/// Sessions remembered at once; the least recently used are forgotten.
+const MAX_SESSIONS: usize = 10_000;
+/// Filtered pools one session keeps progress on at once.
+const MAX_POOLS: usize = 16;
+
+pub const SESSION_COOKIE: &str = "quote_session";
+pub const SESSION_HEADER: &str = "x-quote-session";
+
+/// Result of a no-repeat pick: the quote, if any matched, and the session
+/// token the client should send next time.
+pub struct SessionPick {
+    pub quote_id: Option<String>,
+    pub session: String,
+}
+
+type Pools = LruCache<String, RoaringBitmap>;
+
+/// Quotes already shown to each random-quote session, so a client cycles
+/// through a filtered pool before seeing anything twice. Each session keeps
+/// a bitmap of quote rowids per filter, at most one bit per quote, so
+/// progress through one pool is untouched by picks from another.
+pub struct RandomSessions {
+    seen: Mutex<LruCache<String, Pools>>,
+}
+
+impl Default for RandomSessions {
+    fn default() -> Self {
+        Self {
+            seen: Mutex::new(LruCache::new(NonZeroUsize::new(MAX_SESSIONS).unwrap())),
+        }
+    }
+}
+
+fn new_token() -> String {
+    format!("{:032x}", fastrand::u128(..))
+}
+
+fn new_pools() -> Pools {
+    LruCache::new(NonZeroUsize::new(MAX_POOLS).unwrap())
+}
+
+/// Identifies the pool a filter draws from. Weighting changes the odds of
+/// a pick, not the pool, so it is left out.
+fn pool_key(filter: &QuoteFilter) -> String {
+    let mut exclude: Vec<&String> = filter.exclude.iter().collect();
+    exclude.sort_unstable();
+    serde_json::json!([filter.tags, filter.speaker, filter.max_len, exclude]).to_string()
+}
+
+impl RandomSessions {
+    /// Picks a quote matching `filter` that the session has not seen yet
+    /// under the same filter. Unknown or empty tokens start a new session.
+    /// When the pool is used up the session starts that pool over.
+    pub async fn pick(
+        &self,
+        index: &QuoteIndex,
+        db: &SqlitePool,
+        filter: &QuoteFilter,
+        token: &str,
+        rng: &mut QuoteRng,
+    ) -> Result<SessionPick, sqlx::Error> {
+        let pool = pool_key(filter);
+        let (session, seen) = {
+            let mut sessions = self.seen.lock().unwrap();
+            match sessions.get_mut(token) {
+                Some(pools) => {
+                    let seen = pools.get(&pool).cloned().unwrap_or_default();
+                    (token.to_string(), seen)
+                }
+                None => {
+                    let session = new_token();
+                    sessions.put(session.clone(), new_pools());
+                    (session, RoaringBitmap::new())
+                }
+            }
+        };
+
+        let mut unseen = filter.clone();
+        unseen.exclude_rows |= &seen;
+        let mut quote_id = index.matching_id(db, &unseen, rng).await?;
+        let starting_over = quote_id.is_none() && !seen.is_empty();
+        if starting_over {
+            quote_id = index.matching_id(db, filter, rng).await?;
+        }
+
+        if let Some(id) = &quote_id {
+            let row = index.row_of(db, id).await?;
+            let mut sessions = self.seen.lock().unwrap();
+            let pools = sessions.get_or_insert_mut(session.clone(), new_pools);
+            let seen = pools.get_or_insert_mut(pool, RoaringBitmap::new);
+            if starting_over {
+                seen.clear();
+            }
+            if let Some(row) = row.and_then(|row| u32::try_from(row).ok()) {
+                seen.insert(row);
+            }
+        }
+
+        Ok(SessionPick { quote_id, session })
+    }
+}
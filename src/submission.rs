@@ -0,0 +1,381 @@
+// submission.rs
+use crate::citation::QuoteSource;
+use crate::duplicate::{DuplicateMatch, DuplicatePolicy, TrigramIndex};
+use crate::error::QuoteAddError;
+use crate::quote::{self, Attribution, JsonQuote, Medium, QuoteAudit, Verification};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json as SqlJson, SqlitePool};
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum SubmissionStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// A quote suggested through the public form or API. `website` is a
+/// honeypot: people never see the field, so anything in it came from a bot.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SubmissionRequest {
+    #[serde(flatten)]
+    pub quote: JsonQuote,
+    #[serde(default)]
+    pub website: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Submission {
+    pub id: i64,
+    pub quote: JsonQuote,
+    pub status: SubmissionStatus,
+    pub submitted_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub reviewed_by: Option<String>,
+    pub reject_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RejectRequest {
+    #[schema(example = "Already in the collection")]
+    pub reason: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SubmissionError {
+    #[error("{0}")]
+    Invalid(&'static str),
+    #[error("Too many submissions, try again later")]
+    RateLimited,
+    #[error("No pending submission with that id")]
+    NotFound,
+    #[error("A quote with id {0} already exists")]
+    Conflict(String),
+    #[error("Quote resembles {}; approve with force=true to add it anyway", duplicate_ids(.0))]
+    Duplicate(Vec<DuplicateMatch>),
+    #[error("database operation failed: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+fn duplicate_ids(matches: &[DuplicateMatch]) -> String {
+    let ids: Vec<&str> = matches.iter().map(|m| m.id.as_str()).collect();
+    ids.join(", ")
+}
+
+impl SubmissionError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            SubmissionError::Invalid(_) => StatusCode::BAD_REQUEST,
+            SubmissionError::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            SubmissionError::NotFound => StatusCode::NOT_FOUND,
+            SubmissionError::Conflict(_) | SubmissionError::Duplicate(_) => StatusCode::CONFLICT,
+            SubmissionError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for SubmissionError {
+    fn into_response(self) -> Response {
+        let error_message = match self {
+            SubmissionError::Database(ref e) => {
+                tracing::error!("Submissions: database error: {}", e);
+                "Internal server error.".to_string()
+            }
+            ref e => e.to_string(),
+        };
+        let body = Json(serde_json::json!({ "error": error_message }));
+        (self.status_code(), body).into_response()
+    }
+}
+
+struct SubmissionRow {
+    id: i64,
+    quote_id: String,
+    whos_there: String,
+    answer_who: String,
+    source: String,
+    said_on: Option<String>,
+    event: Option<String>,
+    medium: Option<Medium>,
+    verification: Verification,
+    sources: SqlJson<Vec<QuoteSource>>,
+    tags: SqlJson<Vec<String>>,
+    status: SubmissionStatus,
+    submitted_at: DateTime<Utc>,
+    reviewed_at: Option<DateTime<Utc>>,
+    reviewed_by: Option<String>,
+    reject_reason: Option<String>,
+}
+
+impl From<SubmissionRow> for Submission {
+    fn from(row: SubmissionRow) -> Self {
+        Self {
+            id: row.id,
+            quote: JsonQuote {
+                id: row.quote_id,
+                whos_there: row.whos_there,
+                answer_who: row.answer_who,
+                tags: row.tags.0.into_iter().collect(),
+                source: row.source,
+                attribution: Attribution {
+                    said_on: row.said_on,
+                    event: row.event,
+                    medium: row.medium,
+                    verification: row.verification,
+                },
+                sources: row.sources.0,
+                audit: QuoteAudit::default(),
+                version: 0,
+                lang: None,
+            },
+            status: row.status,
+            submitted_at: row.submitted_at,
+            reviewed_at: row.reviewed_at,
+            reviewed_by: row.reviewed_by,
+            reject_reason: row.reject_reason,
+        }
+    }
+}
+
+/// Cheap checks that keep obvious junk out of the moderation queue, after
+/// cleaning the attribution and sources the way direct adds do.
+fn check_candidate(quote: &mut JsonQuote) -> Result<(), SubmissionError> {
+    quote.clean().map_err(SubmissionError::Invalid)?;
+    if quote.id.trim().is_empty()
+        || quote.whos_there.trim().is_empty()
+        || quote.answer_who.trim().is_empty()
+    {
+        return Err(SubmissionError::Invalid("id, whos_there and answer_who are required"));
+    }
+    if quote.id.len() > 255 || quote.whos_there.len() > 255 || quote.source.len() > 512 {
+        return Err(SubmissionError::Invalid("a field is too long"));
+    }
+    if quote.answer_who.len() > 2000 {
+        return Err(SubmissionError::Invalid("the quote is too long"));
+    }
+    if quote.answer_who.matches("http").count() > 1 {
+        return Err(SubmissionError::Invalid("the quote contains too many links"));
+    }
+    let is_web_source = quote.source.starts_with("http://") || quote.source.starts_with("https://");
+    if !quote.source.is_empty() && !is_web_source {
+        return Err(SubmissionError::Invalid("source must be an http(s) URL"));
+    }
+    if quote.tags.len() > 20 {
+        return Err(SubmissionError::Invalid("too many tags"));
+    }
+    if quote.sources.len() > 10 {
+        return Err(SubmissionError::Invalid("too many sources"));
+    }
+    Ok(())
+}
+
+/// Queues a candidate quote for review. Honeypot hits are accepted and
+/// silently dropped so bots get no signal. Submitted quotes start out
+/// unverified; only an editor can change that.
+pub async fn submit_quote_to_db(
+    db: &SqlitePool,
+    request: SubmissionRequest,
+    submitter_ip: &str,
+    per_hour: i64,
+) -> Result<(), SubmissionError> {
+    if !request.website.is_empty() {
+        tracing::info!("Submissions: dropped honeypot submission from {}", submitter_ip);
+        return Ok(());
+    }
+    let mut candidate = request.quote;
+    check_candidate(&mut candidate)?;
+    candidate.attribution.verification = Verification::Unverified;
+
+    let now = Utc::now();
+    let window_start = now - TimeDelta::hours(1);
+    let recent = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM quote_submissions WHERE submitter_ip = $1 AND submitted_at > $2",
+        submitter_ip,
+        window_start
+    )
+    .fetch_one(db)
+    .await?;
+    if recent >= per_hour {
+        return Err(SubmissionError::RateLimited);
+    }
+
+    let attribution = &candidate.attribution;
+    let sources = SqlJson(&candidate.sources);
+    let tags = SqlJson(candidate.tags.iter().collect::<Vec<_>>());
+    sqlx::query!(
+        "INSERT INTO quote_submissions (quote_id, whos_there, answer_who, source, said_on, event, medium, verification,
+            sources, tags, status, submitter_ip, submitted_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, 'pending', $11, $12)",
+        candidate.id,
+        candidate.whos_there,
+        candidate.answer_who,
+        candidate.source,
+        attribution.said_on,
+        attribution.event,
+        attribution.medium,
+        attribution.verification,
+        sources,
+        tags,
+        submitter_ip,
+        now,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_submissions_from_db(
+    db: &SqlitePool,
+    status: SubmissionStatus,
+) -> Result<Vec<Submission>, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        SubmissionRow,
+        r#"SELECT id, quote_id, whos_there, answer_who, source,
+            said_on, event, medium as "medium: Medium", verification as "verification: Verification",
+            sources as "sources: SqlJson<Vec<QuoteSource>>",
+            tags as "tags: SqlJson<Vec<String>>",
+            status as "status: SubmissionStatus",
+            submitted_at as "submitted_at: DateTime<Utc>",
+            reviewed_at as "reviewed_at: DateTime<Utc>",
+            reviewed_by, reject_reason
+        FROM quote_submissions WHERE status = $1 ORDER BY submitted_at;"#,
+        status
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows.into_iter().map(Submission::from).collect())
+}
+
+async fn get_pending_submission_from_db(
+    db: &SqlitePool,
+    submission_id: i64,
+) -> Result<Submission, SubmissionError> {
+    let row = sqlx::query_as!(
+        SubmissionRow,
+        r#"SELECT id, quote_id, whos_there, answer_who, source,
+            said_on, event, medium as "medium: Medium", verification as "verification: Verification",
+            sources as "sources: SqlJson<Vec<QuoteSource>>",
+            tags as "tags: SqlJson<Vec<String>>",
+            status as "status: SubmissionStatus",
+            submitted_at as "submitted_at: DateTime<Utc>",
+            reviewed_at as "reviewed_at: DateTime<Utc>",
+            reviewed_by, reject_reason
+        FROM quote_submissions WHERE id = $1 AND status = 'pending';"#,
+        submission_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    row.map(Submission::from).ok_or(SubmissionError::NotFound)
+}
+
+/// Lets an editor fix up a pending submission before approving it.
+pub async fn update_submission_in_db(
+    db: &SqlitePool,
+    submission_id: i64,
+    mut quote: JsonQuote,
+) -> Result<Submission, SubmissionError> {
+    check_candidate(&mut quote)?;
+    let attribution = &quote.attribution;
+    let sources = SqlJson(&quote.sources);
+    let tags = SqlJson(quote.tags.iter().collect::<Vec<_>>());
+    let updated = sqlx::query!(
+        "UPDATE quote_submissions SET quote_id = $2, whos_there = $3, answer_who = $4, source = $5,
+            said_on = $6, event = $7, medium = $8, verification = $9, sources = $10, tags = $11
+        WHERE id = $1 AND status = 'pending'",
+        submission_id,
+        quote.id,
+        quote.whos_there,
+        quote.answer_who,
+        quote.source,
+        attribution.said_on,
+        attribution.event,
+        attribution.medium,
+        attribution.verification,
+        sources,
+        tags,
+    )
+    .execute(db)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(SubmissionError::NotFound);
+    }
+
+    get_pending_submission_from_db(db, submission_id).await
+}
+
+/// Promotes a pending submission into `quotes`, attributed to the editor.
+/// The quote and the status change are committed together, and a
+/// submission rejected meanwhile is not approved.
+pub async fn approve_submission_in_db(
+    db: &SqlitePool,
+    submission_id: i64,
+    editor: &str,
+    duplicates: &DuplicatePolicy,
+    known: &TrigramIndex,
+    force: bool,
+) -> Result<JsonQuote, SubmissionError> {
+    let submission = get_pending_submission_from_db(db, submission_id).await?;
+    let candidate = submission.quote;
+
+    let mut tx = db.begin().await?;
+    let added =
+        quote::add_quote_to_db(&mut tx, candidate.clone(), editor, duplicates, known, force).await;
+    match added {
+        Ok(()) => {}
+        Err(QuoteAddError::IdTaken) => return Err(SubmissionError::Conflict(candidate.id)),
+        Err(QuoteAddError::Duplicate(matches)) => return Err(SubmissionError::Duplicate(matches)),
+        Err(QuoteAddError::Database(e)) => return Err(SubmissionError::Database(e)),
+    }
+
+    let now = Utc::now();
+    let approved = sqlx::query!(
+        "UPDATE quote_submissions SET status = 'approved', reviewed_at = $2, reviewed_by = $3
+        WHERE id = $1 AND status = 'pending'",
+        submission_id,
+        now,
+        editor,
+    )
+    .execute(&mut *tx)
+    .await?;
+    if approved.rows_affected() == 0 {
+        return Err(SubmissionError::NotFound);
+    }
+    tx.commit().await?;
+
+    let (approved, tags) = quote::get_quote_by_id_from_db(db, &candidate.id).await?;
+    Ok(JsonQuote::new(&approved, tags))
+}
+
+pub async fn reject_submission_in_db(
+    db: &SqlitePool,
+    submission_id: i64,
+    editor: &str,
+    reason: &str,
+) -> Result<(), SubmissionError> {
+    let now = Utc::now();
+    let rejected = sqlx::query!(
+        "UPDATE quote_submissions SET status = 'rejected', reviewed_at = $2, reviewed_by = $3, reject_reason = $4
+        WHERE id = $1 AND status = 'pending'",
+        submission_id,
+        now,
+        editor,
+        reason,
+    )
+    .execute(db)
+    .await?;
+    if rejected.rows_affected() == 0 {
+        return Err(SubmissionError::NotFound);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+// telemetry.rs
+use axum::http::HeaderMap;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::str::FromStr;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// A `key=value` OpenTelemetry resource attribute.
+#[derive(Clone, Debug)]
+pub struct ResourceAttribute(KeyValue);
+
+impl FromStr for ResourceAttribute {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, got {:?}", s))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("empty attribute key in {:?}", s));
+        }
+        Ok(Self(KeyValue::new(key.to_string(), value.trim().to_string())))
+    }
+}
+
+/// Where and as what spans are exported over OTLP/HTTP.
+pub struct OtlpConfig {
+    pub endpoint: String,
+    pub service_name: String,
+    pub resource_attributes: Vec<ResourceAttribute>,
+}
+
+/// Builds the span exporter and installs the W3C trace context propagator.
+/// The provider must be shut down on exit to flush buffered spans.
+pub fn tracer_provider(
+    config: &OtlpConfig,
+) -> Result<SdkTracerProvider, opentelemetry_otlp::ExporterBuildError> {
+    let endpoint = format!("{}/v1/traces", config.endpoint.trim_end_matches('/'));
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    let resource = Resource::builder_empty()
+        .with_service_name(config.service_name.clone())
+        .with_attributes(config.resource_attributes.iter().map(|attr| attr.0.clone()))
+        .build();
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build())
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Makes `span` a child of the trace named in the request's `traceparent`
+/// header, if any. Does nothing unless export is enabled.
+pub fn set_remote_parent(span: &Span, headers: &HeaderMap) {
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(headers))
+    });
+    let _ = span.set_parent(parent);
+}
@@ -1,21 +1,219 @@
 // templates.rs
-use crate::quote::Quote;
+use crate::assets::{self, AssetError, Assets};
+use crate::i18n::{Locale, LocaleChoice, Locales};
+use crate::quote::{Attribution, Quote};
+use crate::themes::{Theme, ThemedTemplate, Themes};
+use crate::vote::{VoteDirection, VoteTally};
 use askama::Template;
+use serde::Serialize;
+use std::collections::BTreeMap;
 
-#[derive(Template)]
+/// Templates a theme directory may replace.
+pub const THEMED: &[&str] = &[IndexTemplate::FILE, SubmitTemplate::FILE];
+
+/// Links, theme and locale choices, and UI text shared by every page.
+#[derive(Serialize)]
+pub struct Page {
+    pub stylesheet: String,
+    pub theme_stylesheet: Option<String>,
+    pub favicon: String,
+    pub theme: String,
+    pub themes: Vec<String>,
+    pub locale: String,
+    pub locales: Vec<LocaleChoice>,
+    /// Prefix for links within the site: `/es` on pages reached through a
+    /// locale prefix, so following links keeps the locale, else empty.
+    pub base: String,
+    /// The locale's catalog, by message id.
+    pub text: BTreeMap<String, String>,
+}
+
+impl Page {
+    pub fn new(
+        assets: &Assets,
+        themes: &Themes,
+        theme: &Theme,
+        locales: &Locales,
+        locale: &Locale,
+        prefixed: bool,
+    ) -> Self {
+        let url = |name: &str| {
+            assets.url(name).unwrap_or_else(|| {
+                tracing::error!("Page links asset {}, which is not registered", name);
+                String::new()
+            })
+        };
+        Self {
+            stylesheet: url(assets::STYLESHEET),
+            theme_stylesheet: theme.stylesheet.as_deref().map(url),
+            favicon: url(assets::FAVICON),
+            theme: theme.name.clone(),
+            themes: themes.names(),
+            locale: locale.code.to_string(),
+            locales: locales.choices(),
+            base: if prefixed {
+                format!("/{}", locale.code)
+            } else {
+                String::new()
+            },
+            text: locale.messages().clone(),
+        }
+    }
+}
+
+/// One labelled fact about where a quote comes from, in the page's locale.
+#[derive(Serialize)]
+pub struct AttributionLine {
+    pub label: String,
+    pub value: String,
+}
+
+/// Vote totals under a quote, and which button the visitor has pressed.
+#[derive(Serialize)]
+pub struct VoteButtons {
+    pub upvotes: i64,
+    pub downvotes: i64,
+    pub voted_up: bool,
+    pub voted_down: bool,
+}
+
+impl From<VoteTally> for VoteButtons {
+    fn from(tally: VoteTally) -> Self {
+        Self {
+            upvotes: tally.upvotes,
+            downvotes: tally.downvotes,
+            voted_up: tally.vote == Some(VoteDirection::Up),
+            voted_down: tally.vote == Some(VoteDirection::Down),
+        }
+    }
+}
+
+#[derive(Template, Serialize)]
 #[template(path = "index.html")]
 pub struct IndexTemplate {
+    pub page: Page,
     pub quote: Quote,
-    pub stylesheet: &'static str,
     pub tags: String,
+    /// Language of `quote.answer_who` when it is a translation.
+    pub lang: Option<String>,
+    pub attribution: Vec<AttributionLine>,
+    /// Absent when the page shows no stored quote.
+    pub votes: Option<VoteButtons>,
+}
+
+impl ThemedTemplate for IndexTemplate {
+    const FILE: &'static str = "index.html";
 }
 
 impl IndexTemplate {
-    pub fn new(quote: Quote, tags: String) -> Self {
+    pub fn new(
+        page: Page,
+        quote: Quote,
+        tags: String,
+        lang: Option<String>,
+        votes: Option<VoteTally>,
+    ) -> Self {
+        let text = |key: String| page.text.get(&key).cloned().unwrap_or(key);
+        let known = &quote.attribution;
+        let facts = [
+            ("attribution-said-on", known.said_on.clone()),
+            ("attribution-event", known.event.clone()),
+            ("attribution-medium", known.medium.map(|m| text(format!("medium-{}", m.as_str())))),
+            (
+                "attribution-verification",
+                Some(text(format!("verification-{}", known.verification.as_str()))),
+            ),
+        ];
+        let attribution = facts
+            .into_iter()
+            .filter_map(|(label, value)| {
+                Some(AttributionLine {
+                    label: text(label.to_string()),
+                    value: value?,
+                })
+            })
+            .collect();
         Self {
+            page,
             quote,
-            stylesheet: "/style.css",
             tags,
+            lang,
+            attribution,
+            votes: votes.map(VoteButtons::from),
+        }
+    }
+}
+
+#[derive(Template, Serialize)]
+#[template(path = "submit.html")]
+pub struct SubmitTemplate {
+    pub page: Page,
+    pub message: Option<String>,
+}
+
+impl ThemedTemplate for SubmitTemplate {
+    const FILE: &'static str = "submit.html";
+}
+
+impl SubmitTemplate {
+    pub fn new(page: Page, message: Option<String>) -> Self {
+        Self { page, message }
+    }
+}
+
+/// Renders every page of every theme once with sample content, and fails if
+/// any of them links a static asset that is not registered, which would
+/// otherwise only show up as a broken page.
+pub fn check_asset_links(
+    assets: &Assets,
+    themes: &Themes,
+    locales: &Locales,
+) -> Result<(), AssetError> {
+    let locale = locales.default_locale();
+    for name in themes.names() {
+        let Some(theme) = themes.get(&name) else {
+            continue;
+        };
+        let page = || Page::new(assets, themes, theme, locales, locale, false);
+        let quote = Quote {
+            id: "asset-check".to_string(),
+            whos_there: locale.text("fallback-speaker"),
+            answer_who: locale.text("fallback-text"),
+            source: locale.text("fallback-source"),
+            attribution: Attribution::default(),
+            sources: Vec::new(),
+            created_at: None,
+            updated_at: None,
+            created_by: None,
+            updated_by: None,
+            version: 0,
+        };
+        let votes = VoteTally {
+            quote_id: quote.id.clone(),
+            upvotes: 0,
+            downvotes: 0,
+            score: 0,
+            vote: None,
+        };
+        let rendered = [
+            (
+                IndexTemplate::FILE,
+                theme.render(&IndexTemplate::new(page(), quote, String::new(), None, Some(votes))),
+            ),
+            (
+                SubmitTemplate::FILE,
+                theme.render(&SubmitTemplate::new(page(), Some(String::new()))),
+            ),
+        ];
+        for (template, html) in rendered {
+            if let Some(link) = assets.unregistered_link(&html) {
+                return Err(AssetError::Unregistered {
+                    theme: name,
+                    template,
+                    link: link.to_string(),
+                });
+            }
         }
     }
+    Ok(())
 }
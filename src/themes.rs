@@ -0,0 +1,189 @@
+// themes.rs
+use crate::assets::Assets;
+use crate::templates;
+use askama::Template;
+use axum::body::Bytes;
+use minijinja::Environment;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const THEME_COOKIE: &str = "quote_theme";
+pub const DEFAULT_THEME: &str = "light";
+
+/// Built-in themes, compiled in from `themes/<name>/theme.css`.
+const BUILTIN: &[(&str, &str)] = &[
+    ("light", include_str!("../themes/light/theme.css")),
+    ("dark", include_str!("../themes/dark/theme.css")),
+];
+
+const STYLESHEET_FILE: &str = "theme.css";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ThemeError {
+    #[error("failed to read theme directory {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("template {template} in theme {theme} does not compile: {source}")]
+    Template {
+        theme: String,
+        template: &'static str,
+        source: minijinja::Error,
+    },
+    #[error("default theme {0} does not exist")]
+    UnknownDefault(String),
+}
+
+/// A page template that a theme may replace with a runtime-loaded one of
+/// the same file name. The template's fields are its render context.
+pub trait ThemedTemplate: Template + Serialize {
+    const FILE: &'static str;
+}
+
+pub struct Theme {
+    pub name: String,
+    /// Asset name of the theme's stylesheet, linked after the base one.
+    pub stylesheet: Option<String>,
+    /// Runtime-loaded replacements for built-in templates.
+    templates: Environment<'static>,
+}
+
+impl Theme {
+    /// Renders with the theme's own template for `T`, or the built-in one.
+    pub fn render<T: ThemedTemplate>(&self, template: &T) -> String {
+        if let Ok(themed) = self.templates.get_template(T::FILE) {
+            match themed.render(template) {
+                Ok(html) => return html,
+                Err(e) => tracing::error!(
+                    "Theme {}: failed to render {}, using the built-in template: {}",
+                    self.name,
+                    T::FILE,
+                    e
+                ),
+            }
+        }
+        template.render().unwrap()
+    }
+}
+
+/// Themes available to this deployment, by name.
+pub struct Themes {
+    themes: BTreeMap<String, Theme>,
+    default: String,
+}
+
+fn read_optional(path: &Path) -> Result<Option<String>, ThemeError> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(source) => Err(ThemeError::Io {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// Theme names end up in URLs, cookies and asset names.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+}
+
+fn register_stylesheet(assets: &mut Assets, theme: &str, css: String) -> String {
+    let name = format!("theme-{}.css", theme);
+    assets.insert(name.clone(), "text/css; charset=utf-8", Bytes::from(css));
+    name
+}
+
+impl Themes {
+    /// Loads the built-in themes, then every subdirectory of `dir` as a
+    /// theme named after it, replacing a built-in theme of the same name.
+    /// Theme stylesheets are registered with `assets`.
+    pub fn load(
+        dir: Option<&Path>,
+        default: &str,
+        assets: &mut Assets,
+    ) -> Result<Self, ThemeError> {
+        let mut themes = BTreeMap::new();
+        for &(name, css) in BUILTIN {
+            let stylesheet = register_stylesheet(assets, name, css.to_string());
+            themes.insert(
+                name.to_string(),
+                Theme {
+                    name: name.to_string(),
+                    stylesheet: Some(stylesheet),
+                    templates: Environment::new(),
+                },
+            );
+        }
+
+        if let Some(dir) = dir {
+            let io_error = |source| ThemeError::Io {
+                path: dir.to_path_buf(),
+                source,
+            };
+            for entry in std::fs::read_dir(dir).map_err(io_error)? {
+                let path = entry.map_err(io_error)?.path();
+                let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if !path.is_dir() || name.starts_with('.') {
+                    continue;
+                }
+                if !valid_name(name) {
+                    tracing::warn!("Skipping theme {:?}: names may only use a-z, 0-9, - and _", name);
+                    continue;
+                }
+                let name = name.to_string();
+
+                let stylesheet = read_optional(&path.join(STYLESHEET_FILE))?
+                    .map(|css| register_stylesheet(assets, &name, css));
+                let mut env = Environment::new();
+                for &template in templates::THEMED {
+                    let Some(source) = read_optional(&path.join(template))? else {
+                        continue;
+                    };
+                    env.add_template_owned(template, source)
+                        .map_err(|source| ThemeError::Template {
+                            theme: name.clone(),
+                            template,
+                            source,
+                        })?;
+                }
+                tracing::info!("Loaded theme {} from {}", name, path.display());
+                themes.insert(
+                    name.clone(),
+                    Theme {
+                        name,
+                        stylesheet,
+                        templates: env,
+                    },
+                );
+            }
+        }
+
+        if !themes.contains_key(default) {
+            return Err(ThemeError::UnknownDefault(default.to_string()));
+        }
+        Ok(Self {
+            themes,
+            default: default.to_string(),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Theme> {
+        self.themes.get(name)
+    }
+
+    pub fn default_theme(&self) -> &Theme {
+        &self.themes[&self.default]
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.themes.keys().cloned().collect()
+    }
+}
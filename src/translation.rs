@@ -0,0 +1,340 @@
+// translation.rs
+use crate::metrics::QueryTimer;
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+/// A quote's text in another language. The speaker and source are shared
+/// with the original.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QuoteTranslation {
+    #[schema(example = "es")]
+    pub lang: String,
+    pub answer_who: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub created_by: String,
+    pub updated_by: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TranslationRequest {
+    pub answer_who: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TranslationError {
+    #[error("{0:?} is not a valid BCP-47 language tag")]
+    InvalidLanguage(String),
+    #[error("Translated text must not be empty")]
+    EmptyText,
+    #[error("Quote not found")]
+    QuoteNotFound,
+    #[error("No translation of this quote into {0}")]
+    NotFound(String),
+    #[error("database operation failed: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for TranslationError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            TranslationError::InvalidLanguage(_) | TranslationError::EmptyText => {
+                StatusCode::BAD_REQUEST
+            }
+            TranslationError::QuoteNotFound | TranslationError::NotFound(_) => {
+                StatusCode::NOT_FOUND
+            }
+            TranslationError::Database(ref e) => {
+                tracing::error!("Translations: database error: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        let error_message = match self {
+            TranslationError::Database(_) => "Internal server error.".to_string(),
+            e => e.to_string(),
+        };
+        let body = Json(serde_json::json!({ "error": error_message }));
+        (status, body).into_response()
+    }
+}
+
+/// Checks the syntax of a BCP-47 tag (a 2-3 or 5-8 letter language
+/// followed by 1-8 character alphanumeric subtags) and lowercases it, the
+/// form tags are stored and compared in.
+pub fn normalize_lang(tag: &str) -> Option<String> {
+    let tag = tag.trim().to_ascii_lowercase();
+    let mut subtags = tag.split('-');
+    let language = subtags.next()?;
+    let language_ok =
+        matches!(language.len(), 2..=3 | 5..=8) && language.bytes().all(|b| b.is_ascii_lowercase());
+    let subtags_ok = subtags.all(|subtag| {
+        (1..=8).contains(&subtag.len()) && subtag.bytes().all(|b| b.is_ascii_alphanumeric())
+    });
+    (language_ok && subtags_ok).then_some(tag)
+}
+
+/// Languages the client asked for, most preferred first: `?lang=` if given,
+/// then the `Accept-Language` entries ordered by q-value. Wildcards and
+/// entries with `q=0` are dropped.
+pub fn preferred_languages(headers: &HeaderMap, requested: Option<&str>) -> Vec<String> {
+    let mut weighted: Vec<(String, f32)> = headers
+        .get_all(header::ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let lang = normalize_lang(parts.next()?)?;
+            let q = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (q > 0.0).then_some((lang, q))
+        })
+        .collect();
+    weighted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    requested
+        .and_then(normalize_lang)
+        .into_iter()
+        .chain(weighted.into_iter().map(|(lang, _)| lang))
+        .collect()
+}
+
+/// RFC 4647 lookup: tries each preferred tag, then shorter forms of it
+/// (`es-mx` falls back to `es`), and returns the first one available.
+fn lookup<'a>(
+    available: &'a [QuoteTranslation],
+    preferred: &[String],
+) -> Option<&'a QuoteTranslation> {
+    preferred.iter().find_map(|tag| {
+        let mut range = tag.as_str();
+        loop {
+            if let Some(found) = available.iter().find(|t| t.lang == range) {
+                return Some(found);
+            }
+            range = range.rsplit_once('-')?.0;
+        }
+    })
+}
+
+async fn check_quote_exists(db: &SqlitePool, quote_id: &str) -> Result<(), TranslationError> {
+    sqlx::query_scalar!(
+        "SELECT id FROM quotes WHERE id = $1 AND deleted_at IS NULL",
+        quote_id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|_| ())
+    .ok_or(TranslationError::QuoteNotFound)
+}
+
+async fn translations_of(
+    db: &SqlitePool,
+    quote_id: &str,
+) -> Result<Vec<QuoteTranslation>, sqlx::Error> {
+    sqlx::query_as!(
+        QuoteTranslation,
+        r#"SELECT lang, answer_who,
+            created_at AS "created_at: DateTime<Utc>", updated_at AS "updated_at: DateTime<Utc>",
+            created_by, updated_by
+        FROM quote_translations WHERE quote_id = $1 ORDER BY lang"#,
+        quote_id
+    )
+    .fetch_all(db)
+    .await
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_translations"))]
+pub async fn get_translations_from_db(
+    db: &SqlitePool,
+    quote_id: &str,
+) -> Result<Vec<QuoteTranslation>, TranslationError> {
+    let _timer = QueryTimer::start("get_translations");
+    check_quote_exists(db, quote_id).await?;
+    Ok(translations_of(db, quote_id).await?)
+}
+
+/// The translation best matching `preferred`, if the quote has one.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "find_translation"))]
+pub async fn find_translation(
+    db: &SqlitePool,
+    quote_id: &str,
+    preferred: &[String],
+) -> Result<Option<QuoteTranslation>, sqlx::Error> {
+    if preferred.is_empty() {
+        return Ok(None);
+    }
+    let _timer = QueryTimer::start("find_translation");
+    let available = translations_of(db, quote_id).await?;
+    Ok(lookup(&available, preferred).cloned())
+}
+
+/// Adds or replaces the translation of a quote into `lang`.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "put_translation"))]
+pub async fn put_translation_in_db(
+    db: &SqlitePool,
+    quote_id: &str,
+    lang: &str,
+    request: TranslationRequest,
+    author: &str,
+) -> Result<QuoteTranslation, TranslationError> {
+    let _timer = QueryTimer::start("put_translation");
+    let lang =
+        normalize_lang(lang).ok_or_else(|| TranslationError::InvalidLanguage(lang.to_string()))?;
+    let answer_who = request.answer_who.trim();
+    if answer_who.is_empty() {
+        return Err(TranslationError::EmptyText);
+    }
+
+    let now = Utc::now();
+    let written = sqlx::query!(
+        "INSERT INTO quote_translations (quote_id, lang, answer_who, created_at, updated_at, created_by, updated_by)
+        SELECT id, $2, $3, $4, $4, $5, $5 FROM quotes WHERE id = $1 AND deleted_at IS NULL
+        ON CONFLICT (quote_id, lang) DO UPDATE SET
+            answer_who = excluded.answer_who, updated_at = excluded.updated_at, updated_by = excluded.updated_by",
+        quote_id,
+        lang,
+        answer_who,
+        now,
+        author,
+    )
+    .execute(db)
+    .await?;
+    if written.rows_affected() == 0 {
+        return Err(TranslationError::QuoteNotFound);
+    }
+
+    translations_of(db, quote_id)
+        .await?
+        .into_iter()
+        .find(|t| t.lang == lang)
+        .ok_or(TranslationError::NotFound(lang))
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "delete_translation"))]
+pub async fn delete_translation_from_db(
+    db: &SqlitePool,
+    quote_id: &str,
+    lang: &str,
+) -> Result<(), TranslationError> {
+    let _timer = QueryTimer::start("delete_translation");
+    let lang =
+        normalize_lang(lang).ok_or_else(|| TranslationError::InvalidLanguage(lang.to_string()))?;
+    check_quote_exists(db, quote_id).await?;
+    let deleted = sqlx::query!(
+        "DELETE FROM quote_translations WHERE quote_id = $1 AND lang = $2",
+        quote_id,
+        lang
+    )
+    .execute(db)
+    .await?;
+    if deleted.rows_affected() == 0 {
+        return Err(TranslationError::NotFound(lang));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT_LANGUAGE, value.parse().unwrap());
+        headers
+    }
+
+    fn translation(lang: &str) -> QuoteTranslation {
+        QuoteTranslation {
+            lang: lang.to_string(),
+            answer_who: format!("in {}", lang),
+            created_at: DateTime::UNIX_EPOCH,
+            updated_at: DateTime::UNIX_EPOCH,
+            created_by: "tester".to_string(),
+            updated_by: "tester".to_string(),
+        }
+    }
+
+    fn langs(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_tags_are_lowercased() {
+        assert_eq!(normalize_lang(" es-MX "), Some("es-mx".to_string()));
+        assert_eq!(normalize_lang("zh-Hant-TW"), Some("zh-hant-tw".to_string()));
+        assert_eq!(normalize_lang("yue"), Some("yue".to_string()));
+        assert_eq!(normalize_lang("de-CH-1901"), Some("de-ch-1901".to_string()));
+    }
+
+    #[test]
+    fn invalid_tags_are_refused() {
+        for tag in [
+            "",
+            "*",
+            "e",
+            "abcd",
+            "en-",
+            "en--us",
+            "en_US",
+            "12",
+            "en-toolongsubtag",
+        ] {
+            assert_eq!(normalize_lang(tag), None, "{:?}", tag);
+        }
+    }
+
+    #[test]
+    fn preferences_follow_q_values() {
+        let headers = accept("fr;q=0.5, es-MX, en;q=0.8");
+        assert_eq!(
+            preferred_languages(&headers, None),
+            langs(&["es-mx", "en", "fr"])
+        );
+    }
+
+    #[test]
+    fn requested_language_comes_first() {
+        let headers = accept("es-MX, en;q=0.8");
+        assert_eq!(
+            preferred_languages(&headers, Some("DE")),
+            langs(&["de", "es-mx", "en"])
+        );
+        assert_eq!(
+            preferred_languages(&headers, Some("not a tag")),
+            langs(&["es-mx", "en"])
+        );
+    }
+
+    #[test]
+    fn wildcards_zero_q_and_invalid_entries_are_dropped() {
+        let headers = accept("*, de;q=0, fr;q=0.0, en_US, it;q=0.3");
+        assert_eq!(preferred_languages(&headers, None), langs(&["it"]));
+        assert!(preferred_languages(&HeaderMap::new(), None).is_empty());
+    }
+
+    #[test]
+    fn lookup_falls_back_to_shorter_tags() {
+        let available = [translation("es"), translation("pt-br")];
+        let found = lookup(&available, &langs(&["es-mx"])).unwrap();
+        assert_eq!(found.lang, "es");
+        let found = lookup(&available, &langs(&["zh-hant-tw", "pt-br"])).unwrap();
+        assert_eq!(found.lang, "pt-br");
+        assert!(lookup(&available, &langs(&["pt"])).is_none());
+    }
+
+    #[test]
+    fn lookup_tries_each_preference_fully_before_the_next() {
+        let available = [translation("en"), translation("es")];
+        let found = lookup(&available, &langs(&["es-mx", "en"])).unwrap();
+        assert_eq!(found.lang, "es");
+        assert!(lookup(&available, &[]).is_none());
+    }
+}
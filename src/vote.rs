@@ -0,0 +1,278 @@
+// vote.rs
+use crate::metrics::QueryTimer;
+use crate::quote::JsonQuote;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::SqlitePool;
+use std::net::IpAddr;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteDirection {
+    Up,
+    Down,
+}
+
+impl VoteDirection {
+    /// The value stored in `quote_votes`.
+    fn value(self) -> i64 {
+        match self {
+            VoteDirection::Up => 1,
+            VoteDirection::Down => -1,
+        }
+    }
+
+    fn from_value(value: i64) -> Option<Self> {
+        match value {
+            1 => Some(VoteDirection::Up),
+            -1 => Some(VoteDirection::Down),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VoteDirection::Up => "up",
+            VoteDirection::Down => "down",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VoteRequest {
+    pub vote: VoteDirection,
+}
+
+/// A quote's vote totals, and the vote of whoever asked.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VoteTally {
+    pub quote_id: String,
+    pub upvotes: i64,
+    pub downvotes: i64,
+    /// Upvotes minus downvotes.
+    pub score: i64,
+    /// The caller's vote, absent if they have not voted.
+    pub vote: Option<VoteDirection>,
+}
+
+/// Period of votes counted by the top quotes ranking.
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TopWindow {
+    Day,
+    Week,
+    Month,
+    Year,
+    #[default]
+    All,
+}
+
+impl TopWindow {
+    /// Earliest vote counted when ranking at `now`; `None` counts them all.
+    pub fn since(self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let days = match self {
+            TopWindow::Day => 1,
+            TopWindow::Week => 7,
+            TopWindow::Month => 30,
+            TopWindow::Year => 365,
+            TopWindow::All => return None,
+        };
+        Some(now - TimeDelta::days(days))
+    }
+}
+
+/// A quote's place in the ranking, with the votes cast in the window.
+#[derive(Debug)]
+pub struct TopEntry {
+    pub quote_id: String,
+    pub upvotes: i64,
+    pub downvotes: i64,
+}
+
+/// A ranked quote with the votes it got in the ranking's window.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TopQuote {
+    #[serde(flatten)]
+    pub quote: JsonQuote,
+    pub upvotes: i64,
+    pub downvotes: i64,
+    pub score: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VoteError {
+    #[error("Quote not found")]
+    QuoteNotFound,
+    #[error("database operation failed: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl IntoResponse for VoteError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            VoteError::QuoteNotFound => StatusCode::NOT_FOUND,
+            VoteError::Database(ref e) => {
+                tracing::error!("Votes: database error: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        let error_message = match self {
+            VoteError::Database(_) => "Internal server error.".to_string(),
+            e => e.to_string(),
+        };
+        let body = Json(serde_json::json!({ "error": error_message }));
+        (status, body).into_response()
+    }
+}
+
+/// Who a vote counts against: the token subject when there is one, else
+/// an HMAC of the client address under `key`, so anonymous votes are
+/// deduplicated without storing addresses or anything that leads back to
+/// them without the server's secret.
+pub fn voter(subject: Option<&str>, client: IpAddr, key: &[u8]) -> String {
+    if let Some(sub) = subject {
+        return format!("user:{}", sub);
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(client.to_string().as_bytes());
+    let digest = format!("{:x}", mac.finalize().into_bytes());
+    format!("anon:{}", &digest[..32])
+}
+
+/// How strongly a weighted random pick favours a quote: 1 with no votes,
+/// growing with upvotes and shrinking with downvotes.
+pub fn popularity_weight(upvotes: i64, downvotes: i64) -> f64 {
+    (upvotes.max(0) + 1) as f64 / (downvotes.max(0) + 1) as f64
+}
+
+async fn tally_of<'e, E>(executor: E, quote_id: &str, voter: &str) -> Result<VoteTally, VoteError>
+where
+    E: sqlx::SqliteExecutor<'e>,
+{
+    let row = sqlx::query!(
+        r#"SELECT q.upvotes, q.downvotes,
+            (SELECT value FROM quote_votes WHERE quote_id = q.id AND voter = $2) as "vote?: i64"
+        FROM quotes q WHERE q.id = $1 AND q.deleted_at IS NULL"#,
+        quote_id,
+        voter
+    )
+    .fetch_optional(executor)
+    .await?
+    .ok_or(VoteError::QuoteNotFound)?;
+    Ok(VoteTally {
+        quote_id: quote_id.to_string(),
+        upvotes: row.upvotes,
+        downvotes: row.downvotes,
+        score: row.upvotes - row.downvotes,
+        vote: row.vote.and_then(VoteDirection::from_value),
+    })
+}
+
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_votes"))]
+pub async fn get_votes_from_db(
+    db: &SqlitePool,
+    quote_id: &str,
+    voter: &str,
+) -> Result<VoteTally, VoteError> {
+    let _timer = QueryTimer::start("get_votes");
+    tally_of(db, quote_id, voter).await
+}
+
+/// Records `voter`'s vote on a quote, replacing an earlier one, or takes
+/// it back with `None`, and updates the quote's totals. Repeating a vote
+/// changes nothing, not even when it was cast.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "cast_vote"))]
+pub async fn cast_vote_in_db(
+    db: &SqlitePool,
+    quote_id: &str,
+    voter: &str,
+    vote: Option<VoteDirection>,
+) -> Result<VoteTally, VoteError> {
+    let _timer = QueryTimer::start("cast_vote");
+    let mut tx = db.begin().await?;
+    let live = sqlx::query_scalar!(
+        "SELECT id FROM quotes WHERE id = $1 AND deleted_at IS NULL",
+        quote_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    if live.is_none() {
+        return Err(VoteError::QuoteNotFound);
+    }
+
+    match vote {
+        Some(direction) => {
+            let value = direction.value();
+            let now = Utc::now();
+            sqlx::query!(
+                "INSERT INTO quote_votes (quote_id, voter, value, voted_at) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (quote_id, voter) DO UPDATE SET
+                    voted_at = CASE WHEN value = excluded.value THEN voted_at ELSE excluded.voted_at END,
+                    value = excluded.value",
+                quote_id,
+                voter,
+                value,
+                now,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        None => {
+            sqlx::query!(
+                "DELETE FROM quote_votes WHERE quote_id = $1 AND voter = $2",
+                quote_id,
+                voter
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    sqlx::query!(
+        "UPDATE quotes SET
+            upvotes = (SELECT COUNT(*) FROM quote_votes WHERE quote_id = $1 AND value = 1),
+            downvotes = (SELECT COUNT(*) FROM quote_votes WHERE quote_id = $1 AND value = -1)
+        WHERE id = $1",
+        quote_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let tally = tally_of(&mut *tx, quote_id, voter).await?;
+    tx.commit().await?;
+    let counted = vote.map_or("none", VoteDirection::as_str);
+    metrics::counter!("quote_votes_total", "vote" => counted).increment(1);
+    Ok(tally)
+}
+
+/// Live quotes with votes cast since `since`, highest score first, ties
+/// going to the quote with more upvotes.
+#[tracing::instrument(name = "db.query", skip_all, fields(db.system = "sqlite", db.operation = "get_top_quotes"))]
+pub async fn get_top_quotes_from_db(
+    db: &SqlitePool,
+    since: Option<DateTime<Utc>>,
+    limit: i64,
+) -> Result<Vec<TopEntry>, sqlx::Error> {
+    let _timer = QueryTimer::start("get_top_quotes");
+    sqlx::query_as!(
+        TopEntry,
+        r#"SELECT v.quote_id as "quote_id!: String", v.upvotes as "upvotes!: i64", v.downvotes as "downvotes!: i64"
+        FROM (
+            SELECT quote_id, SUM(value = 1) AS upvotes, SUM(value = -1) AS downvotes
+            FROM quote_votes WHERE $1 IS NULL OR voted_at >= $1
+            GROUP BY quote_id
+        ) v JOIN quotes q ON q.id = v.quote_id AND q.deleted_at IS NULL
+        ORDER BY v.upvotes - v.downvotes DESC, v.upvotes DESC, v.quote_id
+        LIMIT $2;"#,
+        since,
+        limit
+    )
+    .fetch_all(db)
+    .await
+}
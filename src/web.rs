@@ -1,15 +1,27 @@
 // web.rs
-use crate::quote::{self, Quote};
-use crate::templates::IndexTemplate;
+use crate::cache;
+use crate::i18n::{Locale, Locales, LOCALE_COOKIE};
+use crate::logging;
+use crate::quote::{Attribution, JsonQuote, Quote, QuoteAudit, QuoteFilter};
+use crate::ratelimit::ClientIp;
+use crate::rng::{self, QuoteRng};
+use crate::session::{SessionPick, SESSION_COOKIE};
+use crate::submission::{self, SubmissionError, SubmissionRequest};
+use crate::templates::{IndexTemplate, Page, SubmitTemplate};
+use crate::themes::{Theme, Themes, THEME_COOKIE};
+use crate::translation;
+use crate::vote::{self, VoteDirection};
 use crate::AppState;
-use askama::Template;
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
+    extract::{Form, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Redirect, Response},
 };
+use axum_extra::{headers::Cookie, TypedHeader};
 
+use fluent_bundle::FluentArgs;
 use serde::Deserialize;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -17,14 +29,170 @@ use tokio::sync::RwLock;
 pub struct GetQuoteParams {
     id: Option<String>,
     tags: Option<String>,
+    seed: Option<u64>,
+    theme: Option<String>,
+    lang: Option<String>,
+}
+
+/// Page showing `quote_id`, keeping the locale prefix and an explicitly
+/// requested quote language.
+fn quote_uri(base: &str, quote_id: &str, lang: Option<&str>) -> String {
+    match lang {
+        Some(lang) => format!("{}/?id={}&lang={}", base, quote_id, lang),
+        None => format!("{}/?id={}", base, quote_id),
+    }
+}
+
+/// Redirects to a randomly chosen quote; never cached so every visit rolls again.
+fn random_redirect(uri: &str, rng: &QuoteRng) -> Response {
+    (
+        [
+            (header::CACHE_CONTROL, cache::NO_STORE.to_string()),
+            (header::HeaderName::from_static(rng::SEED_HEADER), rng.seed().to_string()),
+        ],
+        Redirect::to(uri),
+    )
+        .into_response()
+}
+
+fn session_cookie(session: &str) -> String {
+    format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age=86400",
+        SESSION_COOKIE, session
+    )
+}
+
+fn theme_cookie(theme: &str) -> String {
+    format!("{}={}; Path=/; SameSite=Lax; Max-Age=31536000", THEME_COOKIE, theme)
+}
+
+/// The theme for this request: the one named by `?theme=` if it exists,
+/// else the one in the theme cookie, else the deployment default. When
+/// `?theme=` switched themes, also returns the cookie remembering it.
+fn choose_theme<'a>(
+    themes: &'a Themes,
+    requested: Option<&str>,
+    cookies: Option<&TypedHeader<Cookie>>,
+) -> (&'a Theme, Option<String>) {
+    let remembered = cookies.and_then(|TypedHeader(cookies)| cookies.get(THEME_COOKIE));
+    if let Some(theme) = requested.and_then(|name| themes.get(name)) {
+        let cookie = (remembered != Some(theme.name.as_str())).then(|| theme_cookie(&theme.name));
+        return (theme, cookie);
+    }
+    let theme = remembered
+        .and_then(|name| themes.get(name))
+        .unwrap_or_else(|| themes.default_theme());
+    (theme, None)
+}
+
+fn locale_cookie(locale: &str) -> String {
+    format!("{}={}; Path=/; SameSite=Lax; Max-Age=31536000", LOCALE_COOKIE, locale)
+}
+
+/// The UI locale for this request: the one named by the path prefix, else
+/// the one in the locale cookie, else the best match for `Accept-Language`.
+/// When the prefix switched locales, also returns the cookie remembering
+/// it. `None` if the prefix names no locale.
+fn choose_locale<'a>(
+    locales: &'a Locales,
+    prefix: Option<&str>,
+    cookies: Option<&TypedHeader<Cookie>>,
+    headers: &HeaderMap,
+) -> Option<(&'a Locale, Option<String>)> {
+    let remembered = cookies.and_then(|TypedHeader(cookies)| cookies.get(LOCALE_COOKIE));
+    if let Some(prefix) = prefix {
+        let locale = locales.get(prefix)?;
+        let cookie = (remembered != Some(locale.code)).then(|| locale_cookie(locale.code));
+        return Some((locale, cookie));
+    }
+    let locale = remembered
+        .and_then(|code| locales.get(code))
+        .unwrap_or_else(|| locales.negotiate(headers));
+    Some((locale, None))
+}
+
+fn append_cookie(mut response: Response, cookie: Option<String>) -> Response {
+    if let Some(value) = cookie.and_then(|cookie| cookie.parse().ok()) {
+        response.headers_mut().append(header::SET_COOKIE, value);
+    }
+    response
+}
+
+/// Marks a page as being in `locale`, which may have come from `Accept-Language`.
+fn content_language(mut response: Response, locale: &Locale) -> Response {
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(locale.code));
+    headers.insert(header::VARY, HeaderValue::from_static("accept-language"));
+    response
+}
+
+/// How a page is presented: its theme and UI locale, and whether the
+/// locale came from a path prefix that links should keep.
+struct Ui<'a> {
+    theme: &'a Theme,
+    locale: &'a Locale,
+    prefixed: bool,
+}
+
+impl Ui<'_> {
+    fn page(&self, app_reader: &AppState) -> Page {
+        Page::new(
+            &app_reader.assets,
+            &app_reader.themes,
+            self.theme,
+            &app_reader.locales,
+            self.locale,
+            self.prefixed,
+        )
+    }
+
+    fn base(&self) -> String {
+        if self.prefixed {
+            format!("/{}", self.locale.code)
+        } else {
+            String::new()
+        }
+    }
 }
 
 pub async fn get_main_page_handler(
     State(app_state): State<Arc<RwLock<AppState>>>,
+    ClientIp(client): ClientIp,
+    prefix: Option<Path<String>>,
+    cookies: Option<TypedHeader<Cookie>>,
+    headers: HeaderMap,
     Query(params): Query<GetQuoteParams>,
-) -> Result<Response, StatusCode> {
+) -> Response {
     let app_reader = app_state.read().await;
+    let prefix = prefix.map(|Path(prefix)| prefix);
+    let Some((locale, locale_cookie)) =
+        choose_locale(&app_reader.locales, prefix.as_deref(), cookies.as_ref(), &headers)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let themes = &app_reader.themes;
+    let (theme, theme_cookie) = choose_theme(themes, params.theme.as_deref(), cookies.as_ref());
+    let ui = Ui {
+        theme,
+        locale,
+        prefixed: prefix.is_some(),
+    };
+    let response = main_page(&app_reader, &ui, client, cookies.as_ref(), &headers, params).await;
+    append_cookie(append_cookie(response, theme_cookie), locale_cookie)
+}
+
+async fn main_page(
+    app_reader: &AppState,
+    ui: &Ui<'_>,
+    client: IpAddr,
+    cookies: Option<&TypedHeader<Cookie>>,
+    headers: &HeaderMap,
+    params: GetQuoteParams,
+) -> Response {
     let db = &app_reader.db;
+    let mut rng = QuoteRng::new(params.seed);
+    let lang = params.lang.as_deref().and_then(translation::normalize_lang);
+    let base = ui.base();
 
     if let Some(tags_query_str) = params.tags {
         if !tags_query_str.trim().is_empty() {
@@ -36,10 +204,11 @@ pub async fn get_main_page_handler(
                 .collect();
 
             if !search_tags_vec.is_empty() {
-                match quote::get_tagged_quote_id_from_db(db, search_tags_vec.into_iter()).await {
+                let quote_index = &app_reader.quote_index;
+                match quote_index.tagged_id(db, search_tags_vec.into_iter(), &mut rng).await {
                     Ok(Some(found_quote_id)) => {
-                        let uri = format!("/?id={}", found_quote_id);
-                        return Ok(Redirect::to(&uri).into_response());
+                        let uri = quote_uri(&base, &found_quote_id, lang.as_deref());
+                        return random_redirect(&uri, &rng);
                     }
                     Ok(None) => {
                         tracing::debug!("Web: No quote found for tags, getting random.");
@@ -54,10 +223,36 @@ pub async fn get_main_page_handler(
 
     if let Some(id_str) = params.id {
         tracing::debug!("Web: Fetching quote by ID: {}", id_str);
-        match quote::get_quote_by_id_from_db(db, &id_str).await {
-            Ok((quote, tags)) => {
-                let template = IndexTemplate::new(quote, tags.join(", "));
-                return Ok(Html(template.render().unwrap()).into_response());
+        logging::record_quote_id(&id_str);
+        match app_reader.quote_index.get_quote(db, &id_str).await {
+            Ok((mut quote, tags)) => {
+                // An explicit ?lang= wins, then the UI locale, then Accept-Language.
+                let requested = lang.as_deref().unwrap_or(ui.locale.code);
+                let languages = translation::preferred_languages(headers, Some(requested));
+                let translated = translation::find_translation(db, &id_str, &languages)
+                    .await
+                    .unwrap_or_else(|e| {
+                        tracing::warn!("Web: translation lookup failed for id {}: {}", id_str, e);
+                        None
+                    })
+                    .map(|translated| {
+                        quote.answer_who = translated.answer_who;
+                        translated.lang
+                    });
+                let voter = vote::voter(None, client, &app_reader.jwt_keys.voter);
+                let votes = vote::get_votes_from_db(db, &id_str, &voter)
+                    .await
+                    .map_err(|e| tracing::warn!("Web: vote lookup failed for id {}: {}", id_str, e))
+                    .ok();
+                let template = IndexTemplate::new(
+                    ui.page(app_reader),
+                    quote,
+                    tags.join(", "),
+                    translated,
+                    votes,
+                );
+                let response = Html(ui.theme.render(&template)).into_response();
+                return content_language(response, ui.locale);
             }
             Err(e) => {
                 tracing::warn!(
@@ -70,26 +265,225 @@ pub async fn get_main_page_handler(
     }
 
     tracing::debug!("Web: Fetching random quote ID for redirect.");
-    match quote::get_random_quote_id_from_db(db).await {
-        Ok(random_id) => {
-            let uri = format!("/?id={}", random_id);
-            Ok(Redirect::to(&uri).into_response())
+    let token = cookies
+        .and_then(|TypedHeader(cookies)| cookies.get(SESSION_COOKIE))
+        .unwrap_or_default();
+    let sessions = &app_reader.random_sessions;
+    let picked = sessions
+        .pick(&app_reader.quote_index, db, &QuoteFilter::default(), token, &mut rng)
+        .await;
+    match picked {
+        Ok(SessionPick {
+            quote_id: Some(random_id),
+            session,
+        }) => {
+            let uri = quote_uri(&base, &random_id, lang.as_deref());
+            let mut response = random_redirect(&uri, &rng);
+            if session != token {
+                if let Ok(value) = session_cookie(&session).parse() {
+                    response.headers_mut().insert(header::SET_COOKIE, value);
+                }
+            }
+            response
         }
-        Err(e) => {
-            tracing::error!("Web: Could not get any random quote from DB: {}", e);
+        failed => {
+            if let Err(e) = failed {
+                tracing::error!("Web: Could not get any random quote from DB: {}", e);
+            }
             let fallback_quote = Quote {
                 id: "error".to_string(),
-                whos_there: "Oh no!".to_string(),
-
-                answer_who:
-                    "The quote you were looking for decided to take a day off. Try another!"
-                        .to_string(),
-
-                source: "The Server".to_string(),
+                whos_there: ui.locale.text("fallback-speaker"),
+                answer_who: ui.locale.text("fallback-text"),
+                source: ui.locale.text("fallback-source"),
+                attribution: Attribution::default(),
+                sources: Vec::new(),
+                created_at: None,
+                updated_at: None,
+                created_by: None,
+                updated_by: None,
+                version: 0,
             };
-            let template = IndexTemplate::new(fallback_quote, "error".to_string());
+            let template = IndexTemplate::new(
+                ui.page(app_reader),
+                fallback_quote,
+                "error".to_string(),
+                None,
+                None,
+            );
 
-            Ok(Html(template.render().unwrap()).into_response())
+            content_language(Html(ui.theme.render(&template)).into_response(), ui.locale)
         }
     }
 }
+
+#[derive(Deserialize, Debug)]
+pub struct VoteForm {
+    id: String,
+    /// `up`, `down`, or `none` to take the vote back.
+    vote: String,
+}
+
+/// Whether a form post came from one of this site's own pages: its
+/// `Origin`, or failing that its `Referer`, names the host it was sent to.
+/// Posts carrying neither are refused too.
+fn same_origin(headers: &HeaderMap) -> bool {
+    let Some(host) = headers.get(header::HOST).and_then(|host| host.to_str().ok()) else {
+        return false;
+    };
+    let Some(source) = headers
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))
+        .and_then(|source| source.to_str().ok())
+    else {
+        return false;
+    };
+    source
+        .split_once("://")
+        .and_then(|(_, rest)| rest.split('/').next())
+        .is_some_and(|authority| authority.eq_ignore_ascii_case(host))
+}
+
+/// Records a vote from the quote page's buttons, which work without
+/// JavaScript, and goes back to the quote. Web votes are anonymous, and
+/// only accepted from the site's own pages so other sites cannot cast
+/// votes from their visitors' addresses.
+pub async fn post_vote_handler(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    ClientIp(client): ClientIp,
+    prefix: Option<Path<String>>,
+    headers: HeaderMap,
+    Form(form): Form<VoteForm>,
+) -> Response {
+    if !same_origin(&headers) {
+        tracing::warn!("Web: refused a vote posted from another site");
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let app_reader = app_state.read().await;
+    let prefix = prefix.map(|Path(prefix)| prefix);
+    if prefix.as_deref().is_some_and(|code| app_reader.locales.get(code).is_none()) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let direction = match form.vote.as_str() {
+        "up" => Some(VoteDirection::Up),
+        "down" => Some(VoteDirection::Down),
+        "none" => None,
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let voter = vote::voter(None, client, &app_reader.jwt_keys.voter);
+    match vote::cast_vote_in_db(&app_reader.db, &form.id, &voter, direction).await {
+        Ok(_) => app_reader.reindex(&form.id).await,
+        Err(e) => tracing::warn!("Web: Could not record vote on {}: {}", form.id, e),
+    }
+    let base = prefix.map(|code| format!("/{}", code)).unwrap_or_default();
+    Redirect::to(&quote_uri(&base, &form.id, None)).into_response()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ThemeParams {
+    theme: Option<String>,
+}
+
+pub async fn get_submit_page_handler(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    prefix: Option<Path<String>>,
+    cookies: Option<TypedHeader<Cookie>>,
+    headers: HeaderMap,
+    Query(params): Query<ThemeParams>,
+) -> Response {
+    let app_reader = app_state.read().await;
+    let prefix = prefix.map(|Path(prefix)| prefix);
+    let Some((locale, locale_cookie)) =
+        choose_locale(&app_reader.locales, prefix.as_deref(), cookies.as_ref(), &headers)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let themes = &app_reader.themes;
+    let (theme, theme_cookie) = choose_theme(themes, params.theme.as_deref(), cookies.as_ref());
+    let ui = Ui {
+        theme,
+        locale,
+        prefixed: prefix.is_some(),
+    };
+    let template = SubmitTemplate::new(ui.page(&app_reader), None);
+    let response = content_language(Html(theme.render(&template)).into_response(), locale);
+    append_cookie(append_cookie(response, theme_cookie), locale_cookie)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SubmitForm {
+    id: String,
+    whos_there: String,
+    answer_who: String,
+    #[serde(default)]
+    source: String,
+    #[serde(default)]
+    tags: String,
+    #[serde(default)]
+    website: String,
+}
+
+pub async fn post_submit_handler(
+    State(app_state): State<Arc<RwLock<AppState>>>,
+    ClientIp(client): ClientIp,
+    prefix: Option<Path<String>>,
+    cookies: Option<TypedHeader<Cookie>>,
+    headers: HeaderMap,
+    Form(form): Form<SubmitForm>,
+) -> Response {
+    let request = SubmissionRequest {
+        quote: JsonQuote {
+            id: form.id.trim().to_string(),
+            whos_there: form.whos_there.trim().to_string(),
+            answer_who: form.answer_who.trim().to_string(),
+            tags: form
+                .tags
+                .split(',')
+                .map(|s| s.trim())
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect(),
+            source: form.source.trim().to_string(),
+            attribution: Attribution::default(),
+            sources: Vec::new(),
+            audit: QuoteAudit::default(),
+            version: 0,
+            lang: None,
+        },
+        website: form.website,
+    };
+
+    let app_reader = app_state.read().await;
+    let prefix = prefix.map(|Path(prefix)| prefix);
+    let Some((locale, _)) =
+        choose_locale(&app_reader.locales, prefix.as_deref(), cookies.as_ref(), &headers)
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let submitter_ip = client.to_string();
+    let per_hour = app_reader.submissions_per_hour;
+    let (status, message) =
+        match submission::submit_quote_to_db(&app_reader.db, request, &submitter_ip, per_hour).await
+        {
+            Ok(()) => (StatusCode::OK, locale.text("submit-thanks")),
+            Err(SubmissionError::Database(e)) => {
+                tracing::error!("Web: Failed to store submission: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, locale.text("submit-failed"))
+            }
+            Err(e) => {
+                let mut args = FluentArgs::new();
+                args.set("reason", e.to_string());
+                (e.status_code(), locale.format("submit-rejected", Some(&args)))
+            }
+        };
+
+    let (theme, _) = choose_theme(&app_reader.themes, None, cookies.as_ref());
+    let ui = Ui {
+        theme,
+        locale,
+        prefixed: prefix.is_some(),
+    };
+    let template = SubmitTemplate::new(ui.page(&app_reader), Some(message));
+    let response = (status, Html(theme.render(&template))).into_response();
+    content_language(response, locale)
+}
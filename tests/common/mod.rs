@@ -0,0 +1,79 @@
+//! Starts the server binary in a scratch directory for integration tests.
+
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// Registration password the servers are started with.
+pub const REG_PASSWORD: &str = "test-password";
+
+/// A running server, killed and its directory removed on drop.
+pub struct Server {
+    pub port: u16,
+    child: Child,
+    dir: PathBuf,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A port nothing is listening on.
+pub fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Starts the server on a free port with a fresh database loaded from
+/// `quotes`, passing it `args` and `envs` on top of the usual settings, and
+/// waits until it accepts connections. `name` keeps the scratch directories
+/// of different tests apart.
+pub async fn start_server(
+    name: &str,
+    quotes: &str,
+    args: &[&str],
+    envs: &[(&str, &str)],
+) -> Server {
+    let dir = std::env::temp_dir().join(format!("quote_server_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("jwt_secret.txt"), "test-secret").unwrap();
+    std::fs::write(dir.join("reg_password.txt"), REG_PASSWORD).unwrap();
+    std::fs::write(dir.join("quotes.json"), quotes).unwrap();
+
+    let port = free_port();
+    let child = Command::new(env!("CARGO_BIN_EXE_quote_server"))
+        .current_dir(&dir)
+        .arg("--init-from")
+        .arg(dir.join("quotes.json"))
+        .args(["--port", &port.to_string()])
+        .args(args)
+        .env(
+            "DATABASE_URL",
+            format!("sqlite:{}/quotes.db", dir.display()),
+        )
+        .env("JWT_SECRETFILE", dir.join("jwt_secret.txt"))
+        .env("REG_PASSWORD", dir.join("reg_password.txt"))
+        .env("RUST_LOG", "quote_server=info")
+        .envs(envs.iter().copied())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let server = Server { port, child, dir };
+
+    for _ in 0..100 {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return server;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("server did not start");
+}
@@ -0,0 +1,175 @@
+//! Runs the server's background link checker against a stand-in site and
+//! checks what `/api/v1/broken-links` reports, and that requests to the
+//! site were spaced out by the per-host delay.
+
+mod common;
+
+use axum::{
+    extract::Request,
+    http::{header, Method, StatusCode},
+    middleware::{self, Next},
+    routing::get,
+    Router,
+};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
+
+const HOST_DELAY_MS: u64 = 100;
+
+fn quote(id: &str, source: &str, sources: &[&str]) -> Value {
+    let sources: Vec<Value> = sources.iter().map(|url| json!({ "url": url })).collect();
+    json!({
+        "id": id,
+        "whos_there": "Stand In",
+        "answer_who": "Check my sources.",
+        "tags": [],
+        "source": source,
+        "sources": sources,
+    })
+}
+
+#[tokio::test]
+async fn reports_broken_source_links() {
+    let hits: Arc<Mutex<Vec<Instant>>> = Arc::default();
+    let recorded = hits.clone();
+    let site = Router::new()
+        .route("/ok", get(|| async { StatusCode::OK }))
+        .route("/gone", get(|| async { StatusCode::NOT_FOUND }))
+        .route(
+            "/moved",
+            get(|| async { (StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, "/ok")]) }),
+        )
+        .route(
+            "/loop",
+            get(|| async { (StatusCode::FOUND, [(header::LOCATION, "/loop")]) }),
+        )
+        .route(
+            "/get-only",
+            get(|method: Method| async move {
+                if method == Method::HEAD {
+                    StatusCode::METHOD_NOT_ALLOWED
+                } else {
+                    StatusCode::OK
+                }
+            }),
+        )
+        .layer(middleware::from_fn(move |req: Request, next: Next| {
+            recorded.lock().unwrap().push(Instant::now());
+            next.run(req)
+        }));
+    let site_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let site_url = format!("http://{}", site_listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(site_listener, site).await });
+
+    let closed_port = common::free_port();
+    let url = |path: &str| format!("{}{}", site_url, path);
+    let quotes = json!([
+        quote(
+            "ok-quote",
+            &url("/ok"),
+            &[&url("/moved"), &url("/get-only")]
+        ),
+        quote("gone-quote", &url("/gone"), &[&url("/ok")]),
+        quote("loop-quote", &url("/ok"), &[&url("/loop")]),
+        quote(
+            "down-quote",
+            &format!("http://127.0.0.1:{}/", closed_port),
+            &[]
+        ),
+    ]);
+
+    let server = common::start_server(
+        "links",
+        &quotes.to_string(),
+        &[
+            "--link-check-interval",
+            "3600",
+            "--link-check-host-delay-ms",
+            &HOST_DELAY_MS.to_string(),
+            "--link-check-timeout",
+            "5",
+        ],
+        &[],
+    )
+    .await;
+    let api = format!("http://127.0.0.1:{}/api/v1", server.port);
+    let client = reqwest::Client::new();
+
+    let registered: Value = client
+        .post(format!("{}/register", api))
+        .json(&json!({
+            "full_name": "Link Checker",
+            "email": "links@example.com",
+            "password": common::REG_PASSWORD,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let token = registered["access_token"].as_str().unwrap().to_string();
+
+    let unauthorized = client
+        .get(format!("{}/broken-links", api))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unauthorized.status(), 401);
+
+    let broken = tokio::time::timeout(Duration::from_secs(30), async {
+        loop {
+            let links: Vec<Value> = client
+                .get(format!("{}/broken-links", api))
+                .bearer_auth(&token)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            if links.len() >= 3 {
+                return links;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    })
+    .await
+    .expect("broken links were not reported");
+
+    let find = |suffix: &str| {
+        broken
+            .iter()
+            .find(|link| link["url"].as_str().unwrap().ends_with(suffix))
+            .unwrap_or_else(|| panic!("{} not reported in {:?}", suffix, broken))
+    };
+    let gone = find("/gone");
+    assert_eq!(gone["status"], 404);
+    assert_eq!(gone["quote_ids"], json!(["gone-quote"]));
+    assert!(gone["checked_at"].is_string());
+    assert!(gone["broken_since"].is_string());
+
+    let looped = find("/loop");
+    assert_eq!(looped["status"], 302);
+    assert!(looped["error"].as_str().unwrap().contains("redirects"));
+    assert_eq!(looped["redirects"].as_array().unwrap().len(), 10);
+    assert_eq!(looped["quote_ids"], json!(["loop-quote"]));
+
+    let down = find(&format!(":{}/", closed_port));
+    assert!(down["status"].is_null());
+    assert!(down["error"].is_string());
+
+    assert_eq!(broken.len(), 3, "{:?}", broken);
+
+    let hits = hits.lock().unwrap().clone();
+    for pair in hits.windows(2) {
+        let gap = pair[1].duration_since(pair[0]);
+        assert!(
+            gap >= Duration::from_millis(HOST_DELAY_MS - 10),
+            "requests to one host {:?} apart",
+            gap
+        );
+    }
+}
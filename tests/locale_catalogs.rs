@@ -0,0 +1,52 @@
+//! Checks that every UI locale under `locales/` parses and defines exactly
+//! the same messages, so no page falls back to showing a message id.
+
+use fluent_syntax::ast::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+const CATALOG: &str = "main.ftl";
+
+fn message_ids(path: &Path) -> BTreeSet<String> {
+    let source = std::fs::read_to_string(path).unwrap();
+    let resource = fluent_syntax::parser::parse(source.as_str())
+        .unwrap_or_else(|(_, errors)| panic!("{} does not parse: {:?}", path.display(), errors));
+    resource
+        .body
+        .iter()
+        .filter_map(|entry| match entry {
+            Entry::Message(message) => Some(message.id.name.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn every_locale_has_every_message() {
+    let locales_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("locales");
+    let catalogs: BTreeMap<String, BTreeSet<String>> = std::fs::read_dir(&locales_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .map(|path| {
+            let locale = path.file_name().unwrap().to_string_lossy().into_owned();
+            (locale, message_ids(&path.join(CATALOG)))
+        })
+        .collect();
+    assert!(
+        catalogs.len() >= 2,
+        "expected at least two locales, found {:?}",
+        catalogs.keys()
+    );
+
+    let all_ids: BTreeSet<&String> = catalogs.values().flatten().collect();
+    for (locale, ids) in &catalogs {
+        let missing: Vec<_> = all_ids.iter().filter(|id| !ids.contains(**id)).collect();
+        assert!(
+            missing.is_empty(),
+            "locale {} is missing {:?}",
+            locale,
+            missing
+        );
+    }
+}
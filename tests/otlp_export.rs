@@ -0,0 +1,90 @@
+//! Runs the server against a stand-in OTLP/HTTP collector and checks that a
+//! request's spans are exported under the trace named in its `traceparent`.
+
+mod common;
+
+use axum::{body::Bytes, routing::post, Router};
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+const TRACE_ID: &str = "0af7651916cd43dd8448eb211c80319c";
+
+async fn get(port: u16, path: &str, traceparent: &str) -> Option<String> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.ok()?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\ntraceparent: {}\r\nConnection: close\r\n\r\n",
+        path, traceparent
+    );
+    stream.write_all(request.as_bytes()).await.ok()?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.ok()?;
+    Some(response)
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+#[tokio::test]
+async fn exports_request_spans_under_incoming_trace() {
+    let (exports_tx, mut exports_rx) = mpsc::unbounded_channel::<Bytes>();
+    let collector = Router::new().route(
+        "/v1/traces",
+        post(move |body: Bytes| async move {
+            let _ = exports_tx.send(body);
+        }),
+    );
+    let collector_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let collector_url = format!("http://{}", collector_listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(collector_listener, collector).await });
+
+    let quotes = std::fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/static/quotes.json"),
+    )
+    .unwrap();
+    let server = common::start_server(
+        "otlp",
+        &quotes,
+        &[],
+        &[
+            ("OTEL_EXPORTER_OTLP_ENDPOINT", &collector_url),
+            (
+                "OTEL_RESOURCE_ATTRIBUTES",
+                "deployment.environment=otlp-test",
+            ),
+            ("RUST_LOG", "quote_server=info,tower_http=info"),
+        ],
+    )
+    .await;
+
+    let traceparent = format!("00-{}-b7ad6b7169203331-01", TRACE_ID);
+    let response = get(server.port, "/api/v1/quote/larry-bird-1", &traceparent)
+        .await
+        .expect("server did not answer");
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    let trace_id: Vec<u8> = (0..TRACE_ID.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&TRACE_ID[i..i + 2], 16).unwrap())
+        .collect();
+    let export = tokio::time::timeout(Duration::from_secs(20), async {
+        loop {
+            let body = exports_rx.recv().await.expect("collector stopped");
+            if contains(&body, &trace_id) {
+                return body;
+            }
+        }
+    })
+    .await
+    .expect("no spans exported for the incoming trace");
+
+    assert!(contains(&export, b"db.query"));
+    assert!(contains(&export, b"get_quote_by_id"));
+    assert!(contains(&export, b"deployment.environment"));
+    assert!(contains(&export, b"otlp-test"));
+}
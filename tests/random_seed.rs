@@ -0,0 +1,88 @@
+//! Checks that a seeded random pick is repeatable, that the seed is echoed
+//! back, and that the in-memory index and the SQL fallback agree on it,
+//! including after a quote is added in the middle of the id order.
+
+mod common;
+
+use serde_json::{json, Value};
+use std::path::Path;
+
+async fn pick(client: &reqwest::Client, port: u16, query: &str) -> (String, String) {
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/api/v1/random-quote?{}",
+            port, query
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let seed = response.headers()["x-quote-seed"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let quote: Value = response.json().await.unwrap();
+    (quote["id"].as_str().unwrap().to_string(), seed)
+}
+
+async fn add_quote(client: &reqwest::Client, port: u16, id: &str) {
+    let api = format!("http://127.0.0.1:{}/api/v1", port);
+    let registered: Value = client
+        .post(format!("{}/register", api))
+        .json(&json!({
+            "full_name": "Seed Tester",
+            "email": "seeds@example.com",
+            "password": common::REG_PASSWORD,
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let token = registered["access_token"].as_str().unwrap();
+    let added = client
+        .post(format!("{}/add-quote", api))
+        .bearer_auth(token)
+        .json(&json!({
+            "id": id,
+            "whos_there": "Seed Tester",
+            "answer_who": "Sorted ahead of every other quote, on purpose.",
+            "tags": ["seeded"],
+            "source": "",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(added.status(), 201);
+}
+
+#[tokio::test]
+async fn seeded_picks_repeat_and_match_the_sql_path() {
+    let quotes = std::fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/static/quotes.json"),
+    )
+    .unwrap();
+    let unlimited = ["--rate-limit-read", "off", "--rate-limit-write", "off"];
+    let indexed = common::start_server("seed_index", &quotes, &unlimited, &[]).await;
+    let mut sql_args = unlimited.to_vec();
+    sql_args.push("--no-quote-index");
+    let sql = common::start_server("seed_sql", &quotes, &sql_args, &[]).await;
+    let client = reqwest::Client::new();
+
+    let (first, seed) = pick(&client, indexed.port, "seed=42").await;
+    assert_eq!(seed, "42");
+    let (again, _) = pick(&client, indexed.port, "seed=42").await;
+    assert_eq!(first, again);
+
+    add_quote(&client, indexed.port, "0-seeded").await;
+    add_quote(&client, sql.port, "0-seeded").await;
+    for seed in 0..30 {
+        for filter in ["", "&weighted=true", "&tag=celtics"] {
+            let query = format!("seed={}{}", seed, filter);
+            let (from_index, _) = pick(&client, indexed.port, &query).await;
+            let (from_sql, _) = pick(&client, sql.port, &query).await;
+            assert_eq!(from_index, from_sql, "{}", query);
+        }
+    }
+}
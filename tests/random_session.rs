@@ -0,0 +1,68 @@
+//! Checks that a random-quote session cycles through each filtered pool
+//! without repeats, even while another filter's pool is drained and
+//! started over in between.
+
+mod common;
+
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+fn quote(id: &str, tag: &str) -> Value {
+    json!({
+        "id": id,
+        "whos_there": "Pool Tester",
+        "answer_who": format!("Quote {} from the {} pool.", id, tag),
+        "tags": [tag],
+        "source": "",
+    })
+}
+
+async fn pick(client: &reqwest::Client, port: u16, tag: &str, session: &str) -> (String, String) {
+    let response = client
+        .get(format!(
+            "http://127.0.0.1:{}/api/v1/random-quote?tag={}&session={}",
+            port, tag, session
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let session = response.headers()["x-quote-session"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let quote: Value = response.json().await.unwrap();
+    (quote["id"].as_str().unwrap().to_string(), session)
+}
+
+#[tokio::test]
+async fn sessions_drain_each_filtered_pool_without_repeats() {
+    let quotes = json!([
+        quote("a-1", "alpha"),
+        quote("a-2", "alpha"),
+        quote("a-3", "alpha"),
+        quote("b-1", "beta"),
+        quote("b-2", "beta"),
+    ]);
+    let unlimited = ["--rate-limit-read", "off"];
+    let server = common::start_server("sessions", &quotes.to_string(), &unlimited, &[]).await;
+    let client = reqwest::Client::new();
+
+    for _ in 0..10 {
+        let mut alpha = HashSet::new();
+        let (first, session) = pick(&client, server.port, "alpha", "").await;
+        alpha.insert(first);
+        // Three beta picks use up the beta pool and start it over.
+        let mut beta = Vec::new();
+        for _ in 0..3 {
+            let (id, _) = pick(&client, server.port, "beta", &session).await;
+            beta.push(id);
+        }
+        assert_ne!(beta[0], beta[1]);
+        for _ in 0..2 {
+            let (id, _) = pick(&client, server.port, "alpha", &session).await;
+            assert!(alpha.insert(id.clone()), "alpha quote {} repeated", id);
+        }
+        assert_eq!(alpha.len(), 3);
+    }
+}